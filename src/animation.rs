@@ -0,0 +1,444 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    export::ExportError,
+    scene::Colouring,
+    video::{Encoder, VideoSettings},
+    view::View,
+};
+
+// Keyframes closer together than this, in seconds, are at the same time
+const KEYFRAME_TOLERANCE: f64 = 1e-3;
+
+// Everything that can change from one frame of an animation to the next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub view: View,
+    pub colouring: Colouring,
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+// A zoom from one view to another at a constant rate of magnification
+#[derive(Clone, Copy, Debug)]
+pub struct ZoomAnimation {
+    pub start: Frame,
+    pub end: Frame,
+    // Seconds
+    pub duration: f64,
+    pub frame_rate: u32,
+}
+
+impl ZoomAnimation {
+    pub fn frame_count(&self) -> usize {
+        ((self.duration * self.frame_rate as f64).round() as usize).max(1)
+    }
+
+    // The frame `t` of the way through, from 0 to 1
+    pub fn frame(&self, t: f64) -> Frame {
+        zoom_between(&self.start, &self.end, t)
+    }
+
+    pub fn frames(&self) -> Vec<Frame> {
+        let count = self.frame_count();
+        let last = (count - 1).max(1) as f64;
+        (0..count).map(|i| self.frame(i as f64 / last)).collect()
+    }
+}
+
+// A zoom at a constant rate of magnification from one frame to another, `t` of the way
+fn zoom_between(from: &Frame, to: &Frame, t: f64) -> Frame {
+    let (start, end) = (&from.view, &to.view);
+    let axis_range = start.axis_range * (end.axis_range / start.axis_range).powf(t);
+
+    // The end point drifts towards the middle as the view closes in on it, a straight line
+    // between the centres would overshoot it long before the zoom finishes
+    let share = if start.axis_range == end.axis_range {
+        1.0 - t
+    } else {
+        (axis_range - end.axis_range) / (start.axis_range - end.axis_range)
+    };
+    let centre = [
+        end.centre[0] + (start.centre[0] - end.centre[0]) * share,
+        end.centre[1] + (start.centre[1] - end.centre[1]) * share,
+    ];
+
+    // Zooming is exponential, so the rest changes by the same amount every decade
+    let mut frame = combine([from, from, to, to], |v| lerp(v[1], v[2], t));
+    frame.view.centre = centre;
+    frame.view.axis_range = axis_range;
+    frame
+}
+
+// Combines each parameter of four consecutive frames with `f`, keeping the palette and whether
+// it is a Julia set of the second
+fn combine(frames: [&Frame; 4], f: impl Fn([f64; 4]) -> f64) -> Frame {
+    let each = |get: &dyn Fn(&Frame) -> f64| f(frames.map(get));
+    Frame {
+        view: View {
+            centre: [
+                each(&|frame| frame.view.centre[0]),
+                each(&|frame| frame.view.centre[1]),
+            ],
+            axis_range: each(&|frame| frame.view.axis_range),
+            iterations: each(&|frame| frame.view.iterations as f64).round() as i32,
+            value: each(&|frame| frame.view.value as f64) as f32,
+            exponent: each(&|frame| frame.view.exponent as f64) as f32,
+            julia: frames[1].view.julia,
+            seed: [
+                each(&|frame| frame.view.seed[0]),
+                each(&|frame| frame.view.seed[1]),
+            ],
+        },
+        colouring: Colouring {
+            offset: each(&|frame| frame.colouring.offset as f64) as f32,
+            density: each(&|frame| frame.colouring.density as f64) as f32,
+            ..frames[1].colouring
+        },
+    }
+}
+
+// How the parameters move from a keyframe to the next one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    // A smooth curve through the neighbouring keyframes as well
+    Cubic,
+    // Starts and stops gently
+    Ease,
+    // The zoom changes at a constant rate of magnification, the rest linearly
+    LogZoom,
+}
+
+impl Interpolation {
+    pub const ALL: [Interpolation; 4] = [
+        Interpolation::Linear,
+        Interpolation::Cubic,
+        Interpolation::Ease,
+        Interpolation::LogZoom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Linear => "Linear",
+            Interpolation::Cubic => "Cubic",
+            Interpolation::Ease => "Ease",
+            Interpolation::LogZoom => "Log zoom",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    // Seconds from the start of the timeline
+    pub time: f64,
+    pub frame: Frame,
+    // Used from this keyframe to the next
+    pub interpolation: Interpolation,
+}
+
+// Catmull-Rom spline through b and c, `t` of the way from b
+fn catmull_rom(a: f64, b: f64, c: f64, d: f64, t: f64) -> f64 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * b
+        + (c - a) * t
+        + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+        + (3.0 * b - a - 3.0 * c + d) * t3)
+}
+
+// Keyframes in order of time, and what lies between them
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Timeline {
+    pub fn duration(&self) -> f64 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    // Adds a keyframe, replacing one already at that time
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        match self
+            .keyframes
+            .iter_mut()
+            .find(|existing| (existing.time - keyframe.time).abs() < KEYFRAME_TOLERANCE)
+        {
+            Some(existing) => existing.frame = keyframe.frame,
+            None => {
+                self.keyframes.push(keyframe);
+                self.sort();
+            }
+        }
+    }
+
+    pub fn frame(&self, time: f64) -> Option<Frame> {
+        let first = self.keyframes.first()?;
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time);
+        let Some(next) = next.filter(|&next| next > 0) else {
+            // Before the first keyframe or after the last one
+            let keyframe = if time < first.time {
+                first
+            } else {
+                self.keyframes.last()?
+            };
+            return Some(keyframe.frame);
+        };
+
+        let from = &self.keyframes[next - 1];
+        let to = &self.keyframes[next];
+        let t = (time - from.time) / (to.time - from.time);
+        let pair = [&from.frame, &from.frame, &to.frame, &to.frame];
+        Some(match from.interpolation {
+            Interpolation::Linear => combine(pair, |v| lerp(v[1], v[2], t)),
+            Interpolation::Ease => {
+                let t = t * t * (3.0 - 2.0 * t);
+                combine(pair, |v| lerp(v[1], v[2], t))
+            }
+            Interpolation::LogZoom => zoom_between(&from.frame, &to.frame, t),
+            Interpolation::Cubic => {
+                // The ends of the timeline stand in for the missing neighbours
+                let before = &self.keyframes[next.saturating_sub(2)].frame;
+                let after = &self.keyframes[(next + 1).min(self.keyframes.len() - 1)].frame;
+                let frames = [before, &from.frame, &to.frame, after];
+                let mut frame = combine(frames, |v| catmull_rom(v[0], v[1], v[2], v[3], t));
+                // Through log scale, as a curve between very different ranges can dip below 0
+                let ranges = frames.map(|frame| frame.view.axis_range.ln());
+                frame.view.axis_range =
+                    catmull_rom(ranges[0], ranges[1], ranges[2], ranges[3], t).exp();
+                frame
+            }
+        })
+    }
+
+    pub fn frame_count(&self, frame_rate: u32) -> usize {
+        let Some(first) = self.keyframes.first() else {
+            return 0;
+        };
+        ((self.duration() - first.time) * frame_rate as f64).round() as usize + 1
+    }
+
+    // Every frame of the timeline at `frame_rate`, from the first keyframe to the last
+    pub fn frames(&self, frame_rate: u32) -> Vec<Frame> {
+        let start = self.keyframes.first().map_or(0.0, |first| first.time);
+        (0..self.frame_count(frame_rate))
+            .filter_map(|i| self.frame(start + i as f64 / frame_rate as f64))
+            .collect()
+    }
+}
+
+// Rotates the palette by moving its offset on over time
+#[derive(Clone, Copy, Debug)]
+pub struct PaletteCycle {
+    pub enabled: bool,
+    // Full turns of the gradient per second
+    pub speed: f32,
+    pub reverse: bool,
+}
+
+impl PaletteCycle {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            speed: 0.2,
+            reverse: false,
+        }
+    }
+
+    pub fn advance(&self, offset: f32, seconds: f32) -> f32 {
+        let direction = if self.reverse { -1.0 } else { 1.0 };
+        (offset + direction * self.speed * seconds).rem_euclid(1.0)
+    }
+
+    // Frames of one full turn, which loop seamlessly
+    pub fn frame_count(&self, frame_rate: u32) -> usize {
+        ((frame_rate as f32 / self.speed).round() as usize).max(1)
+    }
+
+    pub fn frames(&self, start: Frame, frame_rate: u32) -> Vec<Frame> {
+        (0..self.frame_count(frame_rate))
+            .map(|i| {
+                let mut frame = start;
+                let seconds = i as f32 / frame_rate as f32;
+                frame.colouring.offset = self.advance(start.colouring.offset, seconds);
+                frame
+            })
+            .collect()
+    }
+}
+
+// Where a recording goes
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    // Numbered PNGs in a directory
+    Images(PathBuf),
+    // A video file encoded by ffmpeg
+    Video {
+        path: PathBuf,
+        settings: VideoSettings,
+    },
+}
+
+impl Destination {
+    pub fn path(&self) -> &Path {
+        match self {
+            Destination::Images(directory) => directory,
+            Destination::Video { path, .. } => path,
+        }
+    }
+}
+
+// A list of frames being rendered to images or a video, one frame at a time
+pub struct Recording {
+    destination: Destination,
+    encoder: Option<Encoder>,
+    frames: Vec<Frame>,
+    size: [u32; 2],
+    next_frame: usize,
+}
+
+impl Recording {
+    pub fn new(
+        destination: Destination,
+        frames: Vec<Frame>,
+        size: [u32; 2],
+    ) -> std::io::Result<Self> {
+        let encoder = match &destination {
+            Destination::Images(directory) => {
+                std::fs::create_dir_all(directory)?;
+                None
+            }
+            Destination::Video { path, settings } => {
+                Some(Encoder::spawn(path, settings, size[0], size[1])?)
+            }
+        };
+        Ok(Self {
+            destination,
+            encoder,
+            frames,
+            size,
+            next_frame: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.destination.path()
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.encoder.is_some()
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn frames_done(&self) -> usize {
+        self.next_frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_frame == self.frames.len()
+    }
+
+    // Where the frame to render next goes, a PNG of its own or the video, and its contents
+    pub fn next_frame(&self) -> Option<(PathBuf, Frame)> {
+        let frame = self.frames.get(self.next_frame)?;
+        let path = match &self.destination {
+            Destination::Images(directory) => {
+                // At least five digits so the files sort in order for video encoders
+                let digits = self.frames.len().to_string().len().max(5);
+                directory.join(format!("frame_{:0digits$}.png", self.next_frame + 1))
+            }
+            Destination::Video { path, .. } => path.clone(),
+        };
+        Some((path, *frame))
+    }
+
+    // Passes the pixels of a video frame on to ffmpeg
+    pub fn write_frame(&mut self, pixels: &[u8]) -> Result<(), ExportError> {
+        match &mut self.encoder {
+            Some(encoder) => encoder.write_frame(pixels),
+            None => Ok(()),
+        }
+    }
+
+    pub fn frame_finished(&mut self) {
+        self.next_frame += 1;
+    }
+
+    // Waits for the video to be written, once every frame is done
+    pub fn finish(self) -> Result<(), ExportError> {
+        match self.encoder {
+            Some(encoder) => encoder.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Scene;
+
+    fn keyframe(time: f64, julia: bool, seed: [f64; 2], interpolation: Interpolation) -> Keyframe {
+        let mut scene = Scene::default();
+        scene.formula.julia = julia;
+        scene.formula.seed = seed;
+        Keyframe {
+            time,
+            frame: Frame {
+                view: scene.view(),
+                colouring: scene.colouring,
+            },
+            interpolation,
+        }
+    }
+
+    #[test]
+    fn interpolates_the_julia_seed() {
+        for interpolation in Interpolation::ALL {
+            let timeline = Timeline {
+                keyframes: vec![
+                    keyframe(0.0, true, [-0.8, 0.0], interpolation),
+                    keyframe(2.0, true, [-0.4, 0.6], interpolation),
+                ],
+            };
+            let view = timeline.frame(1.0).unwrap().view;
+            assert!(view.julia);
+            let [x, y] = view.seed;
+            assert!(
+                -0.8 < x && x < -0.4 && 0.0 < y && y < 0.6,
+                "{:?}",
+                view.seed
+            );
+            assert_eq!(timeline.frame(2.0).unwrap().view.seed, [-0.4, 0.6]);
+        }
+    }
+
+    #[test]
+    fn switches_to_a_julia_set_at_the_next_keyframe() {
+        let timeline = Timeline {
+            keyframes: vec![
+                keyframe(0.0, false, [0.3, 0.0], Interpolation::Linear),
+                keyframe(1.0, true, [0.3, 0.0], Interpolation::Linear),
+            ],
+        };
+        assert!(!timeline.frame(0.99).unwrap().view.julia);
+        assert!(timeline.frame(1.0).unwrap().view.julia);
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scene::{Colouring, Scene},
+    view::View,
+};
+
+pub const FILE_NAME: &str = "bookmarks.toml";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub view: View,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct BookmarkFile {
+    #[serde(default, rename = "bookmark")]
+    bookmarks: Vec<Bookmark>,
+}
+
+pub struct Entry {
+    pub bookmark: Bookmark,
+    // Rendered lazily by State::update
+    pub thumbnail: Option<egui::TextureHandle>,
+}
+
+impl Entry {
+    fn new(bookmark: Bookmark) -> Self {
+        Self {
+            bookmark,
+            thumbnail: None,
+        }
+    }
+}
+
+pub struct Bookmarks {
+    pub famous: Vec<Entry>,
+    pub saved: Vec<Entry>,
+}
+
+impl Bookmarks {
+    pub fn load() -> Self {
+        let file: BookmarkFile = crate::config::load(FILE_NAME);
+        Self {
+            famous: famous_locations().into_iter().map(Entry::new).collect(),
+            saved: file
+                .bookmarks
+                .into_iter()
+                .filter(is_valid)
+                .map(Entry::new)
+                .collect(),
+        }
+    }
+
+    fn save(&self) {
+        let file = BookmarkFile {
+            bookmarks: self
+                .saved
+                .iter()
+                .map(|entry| entry.bookmark.clone())
+                .collect(),
+        };
+        crate::config::save(FILE_NAME, &file);
+    }
+
+    pub fn add(&mut self, bookmark: Bookmark) {
+        self.saved.push(Entry::new(bookmark));
+        self.save();
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.saved.len() {
+            self.saved.remove(index);
+            self.save();
+        }
+    }
+
+    pub fn missing_thumbnail(&mut self) -> Option<&mut Entry> {
+        self.famous
+            .iter_mut()
+            .chain(self.saved.iter_mut())
+            .find(|entry| entry.thumbnail.is_none())
+    }
+}
+
+// Hand-edited bookmarks can hold views the camera cannot show
+fn is_valid(bookmark: &Bookmark) -> bool {
+    let scene = Scene::from_view(&bookmark.view, Colouring::default());
+    match scene.validate() {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("skipping bookmark {}: {}", bookmark.name, e);
+            false
+        }
+    }
+}
+
+fn famous_locations() -> Vec<Bookmark> {
+    // All of these are for the standard z = z^2 + c formula
+    let location = |name: &str, centre: [f64; 2], axis_range: f64, iterations: i32| Bookmark {
+        name: name.to_string(),
+        view: View {
+            centre,
+            axis_range,
+            iterations,
+            value: 2.0,
+            exponent: 2.0,
+            julia: false,
+            seed: [0.0, 0.0],
+        },
+    };
+
+    vec![
+        location("Seahorse Valley", [-0.75, 0.1], 0.1, 500),
+        location("Elephant Valley", [0.275, 0.0], 0.04, 500),
+        location("Triple Spiral Valley", [-0.088, 0.654], 0.02, 800),
+        location("Misiurewicz point M(2,2) at c = i", [0.0, 1.0], 0.02, 800),
+        location(
+            "Misiurewicz point M(23,2)",
+            [-0.77568377, 0.13646737],
+            2e-4,
+            1500,
+        ),
+        location("Period 3 mini-brot", [-1.754877666, 0.0], 0.04, 500),
+        location("Period 4 mini-brot", [-0.156520167, 1.032247109], 0.02, 800),
+        location(
+            "Period 4 mini-brot on the needle",
+            [-1.940799807, 0.0],
+            0.005,
+            1000,
+        ),
+    ]
+}
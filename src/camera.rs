@@ -0,0 +1,366 @@
+// Camera controller, owns the view in f64 and eases it toward its targets.
+// ShaderUniform only receives the f32 copy written out by write_uniform.
+
+// axis_range is scaled by ZOOM_STEP for every line scrolled
+const ZOOM_STEP: f64 = 0.85;
+// Rate constant of the exponential easing toward the target view, per second
+const EASING_RATE: f64 = 12.0;
+// Pan velocity left after one second of drifting
+const PAN_FRICTION: f64 = 0.02;
+// Smoothing applied to the drag velocity estimate
+const VELOCITY_SMOOTHING: f64 = 0.3;
+// Below this speed, in screen heights per second, drifting stops
+const MIN_PAN_SPEED: f64 = 0.01;
+// Releasing after holding the cursor still this long, in seconds, throws nothing
+const RELEASE_WINDOW: f64 = 0.05;
+
+pub struct Camera {
+    pub centre: [f64; 2],
+    pub axis_range: f64,
+    resolution: [f64; 2],
+    target_centre: [f64; 2],
+    target_range: f64,
+    pan_velocity: [f64; 2],
+    dragging: bool,
+}
+
+impl Camera {
+    pub const DEFAULT_CENTRE: [f64; 2] = [-0.765, 0.0];
+    pub const DEFAULT_RANGE: f64 = 2.0;
+
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            centre: Self::DEFAULT_CENTRE,
+            axis_range: Self::DEFAULT_RANGE,
+            resolution: [width as f64, height as f64],
+            target_centre: Self::DEFAULT_CENTRE,
+            target_range: Self::DEFAULT_RANGE,
+            pan_velocity: [0.0, 0.0],
+            dragging: false,
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.resolution = [width as f64, height as f64];
+    }
+
+    // Mirrors get_coordinate in shader.wgsl, x and y are in window pixels
+    pub fn get_coordinate(&self, x: f64, y: f64) -> [f64; 2] {
+        Self::map(self.centre, self.axis_range, self.resolution, x, y)
+    }
+
+    fn map(centre: [f64; 2], axis_range: f64, resolution: [f64; 2], x: f64, y: f64) -> [f64; 2] {
+        let aspect_ratio = resolution[0] / resolution[1];
+        let normalised_x = x / resolution[0] - 0.5;
+        let normalised_y = y / resolution[1] - 0.5;
+        [
+            centre[0] + normalised_x * axis_range * aspect_ratio,
+            centre[1] + normalised_y * axis_range,
+        ]
+    }
+
+    // Inverse of get_coordinate, gives the window pixel showing a fractal coordinate
+    pub fn get_screen_position(&self, coordinate: [f64; 2]) -> [f64; 2] {
+        let aspect_ratio = self.resolution[0] / self.resolution[1];
+        [
+            ((coordinate[0] - self.centre[0]) / (self.axis_range * aspect_ratio) + 0.5)
+                * self.resolution[0],
+            ((coordinate[1] - self.centre[1]) / self.axis_range + 0.5) * self.resolution[1],
+        ]
+    }
+
+    // Zooms the target view by `lines` scroll steps, keeping the point under (x, y) fixed.
+    // Line deltas from mice and fractional pixel deltas from trackpads both land here.
+    pub fn scroll(&mut self, x: f64, y: f64, lines: f64) {
+        self.zoom_target_at(x, y, ZOOM_STEP.powf(lines));
+    }
+
+    // Scales the target axis_range while keeping the fractal coordinate under (x, y) fixed
+    pub fn zoom_target_at(&mut self, x: f64, y: f64, factor: f64) {
+        let before = Self::map(self.target_centre, self.target_range, self.resolution, x, y);
+        self.target_range *= factor;
+        let after = Self::map(self.target_centre, self.target_range, self.resolution, x, y);
+        self.target_centre[0] += before[0] - after[0];
+        self.target_centre[1] += before[1] - after[1];
+    }
+
+    // Eases toward the given view over the next few frames
+    pub fn set_target(&mut self, centre: [f64; 2], axis_range: f64) {
+        self.target_centre = centre;
+        self.target_range = axis_range;
+        self.pan_velocity = [0.0, 0.0];
+    }
+
+    // Moves the view immediately, with no easing
+    pub fn jump_to(&mut self, centre: [f64; 2], axis_range: f64) {
+        self.set_target(centre, axis_range);
+        self.centre = centre;
+        self.axis_range = axis_range;
+    }
+
+    // Eases toward the view where the window pixel rectangle a..b fills the window,
+    // growing it along one axis to keep the window's aspect ratio
+    pub fn zoom_to_rect(&mut self, a: [f64; 2], b: [f64; 2]) {
+        let a = self.get_coordinate(a[0], a[1]);
+        let b = self.get_coordinate(b[0], b[1]);
+        let aspect_ratio = self.resolution[0] / self.resolution[1];
+        let width = (a[0] - b[0]).abs();
+        let height = (a[1] - b[1]).abs();
+        self.set_target(
+            [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0],
+            height.max(width / aspect_ratio),
+        );
+    }
+
+    // Moves the target view by dx and dy screen heights
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        self.target_centre[0] += dx * self.target_range;
+        self.target_centre[1] += dy * self.target_range;
+    }
+
+    // Zooms the target view by `lines` scroll steps about the window centre
+    pub fn zoom(&mut self, lines: f64) {
+        self.scroll(self.resolution[0] / 2.0, self.resolution[1] / 2.0, lines);
+    }
+
+    pub fn reset(&mut self) {
+        self.set_target(Self::DEFAULT_CENTRE, Self::DEFAULT_RANGE);
+    }
+
+    pub fn start_drag(&mut self) {
+        self.dragging = true;
+        self.pan_velocity = [0.0, 0.0];
+    }
+
+    // idle is the time since the cursor last moved
+    pub fn end_drag(&mut self, idle: f64) {
+        self.dragging = false;
+        if idle > RELEASE_WINDOW {
+            self.pan_velocity = [0.0, 0.0];
+        }
+    }
+
+    // dx and dy are the cursor movement in window pixels, dt the time since the last move
+    pub fn drag(&mut self, dx: f64, dy: f64, dt: f64) {
+        if !self.dragging {
+            return;
+        }
+        let offset = [
+            -dx / self.resolution[1] * self.axis_range,
+            -dy / self.resolution[1] * self.axis_range,
+        ];
+        self.centre[0] += offset[0];
+        self.centre[1] += offset[1];
+        self.target_centre[0] += offset[0];
+        self.target_centre[1] += offset[1];
+
+        if dt > 0.0 {
+            for (velocity, offset) in self.pan_velocity.iter_mut().zip(offset) {
+                *velocity += (offset / dt - *velocity) * (1.0 - VELOCITY_SMOOTHING);
+            }
+        }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        !self.dragging && self.pan_velocity == [0.0, 0.0] && self.target_reached()
+    }
+
+    fn target_reached(&self) -> bool {
+        let tolerance = self.axis_range * 1e-4;
+        (self.target_range / self.axis_range - 1.0).abs() < 1e-4
+            && (self.target_centre[0] - self.centre[0]).abs() < tolerance
+            && (self.target_centre[1] - self.centre[1]).abs() < tolerance
+    }
+
+    // Advances drift and easing by dt seconds
+    pub fn update(&mut self, dt: f64) {
+        if !self.dragging {
+            let speed = self.pan_velocity[0].hypot(self.pan_velocity[1]) / self.axis_range;
+            if speed < MIN_PAN_SPEED {
+                self.pan_velocity = [0.0, 0.0];
+            } else {
+                let friction = PAN_FRICTION.powf(dt);
+                for (target, velocity) in self.target_centre.iter_mut().zip(&mut self.pan_velocity)
+                {
+                    *target += *velocity * dt;
+                    *velocity *= friction;
+                }
+            }
+        }
+
+        if !self.target_reached() {
+            // Centre and range move by the same fraction so the zoom anchor stays put
+            let t = 1.0 - (-EASING_RATE * dt).exp();
+            self.centre[0] += (self.target_centre[0] - self.centre[0]) * t;
+            self.centre[1] += (self.target_centre[1] - self.centre[1]) * t;
+            self.axis_range += (self.target_range - self.axis_range) * t;
+        }
+        // Snapping in the same step means a settled camera is exactly on its target
+        if self.target_reached() {
+            self.centre = self.target_centre;
+            self.axis_range = self.target_range;
+        }
+    }
+
+    pub fn write_uniform(&self, mouse: &mut crate::renderer::Mouse, axis_range: &mut f32) {
+        mouse.centre_x = self.centre[0] as f32;
+        mouse.centre_y = self.centre[1] as f32;
+        *axis_range = self.axis_range as f32;
+    }
+}
+
+/// The part of the plane shown in an image of `width` by `height` pixels, the same way the
+/// camera shows it in the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// Point at the middle of the image, as [re, im]
+    pub centre: [f64; 2],
+    /// Height of the image in fractal units, the width follows from the aspect ratio
+    pub axis_range: f64,
+    /// Size of the image in pixels
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// The starting view of the window, showing the whole set
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            centre: Camera::DEFAULT_CENTRE,
+            axis_range: Camera::DEFAULT_RANGE,
+            width,
+            height,
+        }
+    }
+
+    pub(crate) fn from_view(view: &crate::view::View, width: u32, height: u32) -> Self {
+        Self {
+            centre: view.centre,
+            axis_range: view.axis_range,
+            width,
+            height,
+        }
+    }
+
+    /// Magnification relative to the starting view
+    pub fn zoom(&self) -> f64 {
+        Camera::DEFAULT_RANGE / self.axis_range
+    }
+
+    /// Fractal coordinate under the pixel at (x, y), counted from the top left
+    pub fn coordinate(&self, x: f64, y: f64) -> [f64; 2] {
+        let resolution = [self.width as f64, self.height as f64];
+        Camera::map(self.centre, self.axis_range, resolution, x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f64; 2], b: [f64; 2], tolerance: f64) {
+        let distance = (a[0] - b[0]).hypot(a[1] - b[1]);
+        assert!(
+            distance < tolerance,
+            "{:?} is {:e} from {:?}",
+            a,
+            distance,
+            b
+        );
+    }
+
+    // Runs the camera at 60 frames per second until it settles, returning the frames taken
+    fn settle(camera: &mut Camera) -> usize {
+        (1..=600)
+            .find(|_| {
+                camera.update(1.0 / 60.0);
+                camera.is_settled()
+            })
+            .expect("the camera did not settle within 10 seconds")
+    }
+
+    #[test]
+    fn scrolling_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera::new(800.0, 600.0);
+        let cursor = [620.0, 150.0];
+        let before = camera.get_coordinate(cursor[0], cursor[1]);
+        camera.scroll(cursor[0], cursor[1], 3.0);
+        // Also while easing, as centre and range move by the same fraction
+        camera.update(1.0 / 60.0);
+        assert_close(camera.get_coordinate(cursor[0], cursor[1]), before, 1e-12);
+        settle(&mut camera);
+        assert_close(camera.get_coordinate(cursor[0], cursor[1]), before, 1e-12);
+        assert!((camera.axis_range - Camera::DEFAULT_RANGE * ZOOM_STEP.powi(3)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn zooming_to_a_rectangle_keeps_the_aspect_ratio() {
+        // Tall and wide selections on a 2:1 window
+        for (a, b) in [
+            ([100.0, 50.0], [140.0, 250.0]),
+            ([500.0, 300.0], [700.0, 320.0]),
+        ] {
+            let mut camera = Camera::new(800.0, 400.0);
+            let corners = [
+                camera.get_coordinate(a[0], a[1]),
+                camera.get_coordinate(b[0], b[1]),
+            ];
+            camera.zoom_to_rect(a, b);
+            settle(&mut camera);
+            // Both corners are in view, and the selection fills one axis exactly
+            let [left, top] = camera.get_coordinate(0.0, 0.0);
+            let [right, bottom] = camera.get_coordinate(800.0, 400.0);
+            assert!(((right - left) / (bottom - top) - 2.0).abs() < 1e-12);
+            for [x, y] in corners {
+                assert!(left - 1e-12 <= x && x <= right + 1e-12);
+                assert!(top - 1e-12 <= y && y <= bottom + 1e-12);
+            }
+            let width = (corners[0][0] - corners[1][0]).abs();
+            let height = (corners[0][1] - corners[1][1]).abs();
+            let filled =
+                (width - (right - left)).abs() < 1e-12 || (height - (bottom - top)).abs() < 1e-12;
+            assert!(filled, "{:?} does not fill the view", corners);
+        }
+    }
+
+    #[test]
+    fn easing_converges_and_settles() {
+        let mut camera = Camera::new(800.0, 600.0);
+        assert!(camera.is_settled());
+        camera.set_target([-1.25, 0.1], 0.01);
+        assert!(!camera.is_settled());
+        camera.update(1.0 / 60.0);
+        assert!(!camera.is_settled());
+        // Easing at a rate of 12 per second reaches 1e-4 of the zoom within a second or so
+        assert!(settle(&mut camera) < 120);
+        assert_eq!(camera.centre, [-1.25, 0.1]);
+        assert_eq!(camera.axis_range, 0.01);
+    }
+
+    #[test]
+    fn a_thrown_drag_drifts_then_settles() {
+        let mut camera = Camera::new(800.0, 600.0);
+        camera.start_drag();
+        assert!(!camera.is_settled());
+        for _ in 0..5 {
+            camera.drag(-30.0, 0.0, 1.0 / 60.0);
+        }
+        // The drag itself moves the view without easing
+        assert_close(camera.centre, [-0.765 + 150.0 / 600.0 * 2.0, 0.0], 1e-12);
+        camera.end_drag(0.0);
+        assert!(!camera.is_settled());
+        let released = camera.centre;
+        settle(&mut camera);
+        assert!(camera.centre[0] > released[0], "the view did not drift on");
+        assert_eq!(camera.centre[1], 0.0);
+    }
+
+    #[test]
+    fn holding_still_before_release_throws_nothing() {
+        let mut camera = Camera::new(800.0, 600.0);
+        camera.start_drag();
+        camera.drag(-30.0, 0.0, 1.0 / 60.0);
+        camera.end_drag(RELEASE_WINDOW * 2.0);
+        assert!(camera.is_settled());
+    }
+}
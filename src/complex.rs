@@ -0,0 +1,29 @@
+// Complex arithmetic on [re, im] pairs for the CPU-side analysis tools
+
+pub fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+pub fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+pub fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+pub fn div(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let denominator = b[0] * b[0] + b[1] * b[1];
+    [
+        (a[0] * b[0] + a[1] * b[1]) / denominator,
+        (a[1] * b[0] - a[0] * b[1]) / denominator,
+    ]
+}
+
+pub fn scale(a: [f64; 2], factor: f64) -> [f64; 2] {
+    [a[0] * factor, a[1] * factor]
+}
+
+pub fn length(z: [f64; 2]) -> f64 {
+    z[0].hypot(z[1])
+}
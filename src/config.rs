@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+// Files live in the user's config directory, e.g. ~/.config/mandelbrot-explorer on Linux
+pub fn path(file_name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("mandelbrot-explorer").join(file_name))
+}
+
+// Reads a TOML config file, writing out the defaults the first time so they can be edited
+pub fn load<T: Serialize + DeserializeOwned + Default>(file_name: &str) -> T {
+    let Some(path) = path(file_name) else {
+        return T::default();
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+            log::error!("could not parse {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let value = T::default();
+            save(file_name, &value);
+            value
+        }
+        Err(e) => {
+            log::error!("could not read {}: {}", path.display(), e);
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize>(file_name: &str, value: &T) {
+    let Some(path) = path(file_name) else {
+        return;
+    };
+
+    let result = toml::to_string_pretty(value)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            std::fs::write(&path, contents).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = result {
+        log::error!("could not write {}: {}", path.display(), e);
+    }
+}
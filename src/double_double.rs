@@ -0,0 +1,295 @@
+// Double-double arithmetic for the CPU-side analysis tools: a value held as the unevaluated sum
+// hi + lo of two f64s, good for about 32 significant digits
+
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+// Sum of two f64s and its rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_part = sum - a;
+    (sum, (a - (sum - b_part)) + (b - b_part))
+}
+
+// As two_sum, but only valid when |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    (sum, b - (sum - a))
+}
+
+// Product of two f64s and its rounding error
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    (product, a.mul_add(b, -product))
+}
+
+impl DoubleDouble {
+    pub const ZERO: Self = Self { hi: 0.0, lo: 0.0 };
+    pub const ONE: Self = Self { hi: 1.0, lo: 0.0 };
+    // Relative rounding error, 2^-104
+    pub const EPSILON: f64 = 4.930380657631324e-32;
+
+    fn normalised(hi: f64, lo: f64) -> Self {
+        let (hi, lo) = quick_two_sum(hi, lo);
+        Self { hi, lo }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.hi
+    }
+
+    pub fn abs(self) -> Self {
+        if self.hi < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    pub fn is_finite(self) -> bool {
+        self.hi.is_finite() && self.lo.is_finite()
+    }
+
+    pub fn floor(self) -> Self {
+        let hi = self.hi.floor();
+        if hi == self.hi {
+            Self::normalised(hi, self.lo.floor())
+        } else {
+            Self { hi, lo: 0.0 }
+        }
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(value: f64) -> Self {
+        Self { hi: value, lo: 0.0 }
+    }
+}
+
+impl std::str::FromStr for DoubleDouble {
+    type Err = std::num::ParseFloatError;
+
+    // Decimal notation, optionally signed, with an optional exponent
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        // Checks the syntax and catches inf and NaN, which have no digits to accumulate
+        let rough: f64 = text.parse()?;
+        if !rough.is_finite() {
+            return Ok(rough.into());
+        }
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) => (
+                &text[..index],
+                text[index + 1..].parse::<i32>().unwrap_or(0),
+            ),
+            None => (text, 0),
+        };
+        let (negative, mantissa) = match mantissa.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let mut value = Self::ZERO;
+        let mut exponent = exponent;
+        let mut after_point = false;
+        for character in mantissa.chars() {
+            match character {
+                '.' => after_point = true,
+                digit => {
+                    value = value * 10.0 + f64::from(digit.to_digit(10).unwrap_or(0));
+                    if after_point {
+                        exponent -= 1;
+                    }
+                }
+            }
+        }
+        let value = value * power_of_ten(exponent);
+        Ok(if negative { -value } else { value })
+    }
+}
+
+fn power_of_ten(exponent: i32) -> DoubleDouble {
+    let mut power = DoubleDouble::ONE;
+    for _ in 0..exponent.unsigned_abs() {
+        power = power * 10.0;
+    }
+    if exponent < 0 {
+        DoubleDouble::ONE / power
+    } else {
+        power
+    }
+}
+
+impl fmt::Display for DoubleDouble {
+    // Fixed point, with 30 decimals unless a precision is given, about all it holds near 1
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_finite() {
+            return fmt::Display::fmt(&self.hi, f);
+        }
+        let decimals = f.precision().unwrap_or(30);
+        let rounded = self.abs() + DoubleDouble::from(0.5) * power_of_ten(-(decimals as i32));
+        let integer = rounded.floor();
+        let mut fraction = rounded - integer;
+        // The analysis tools only need this for values well inside f64's exact integer range
+        let mut text = format!("{:.0}", integer.hi + integer.lo);
+        if decimals > 0 {
+            text.push('.');
+        }
+        for _ in 0..decimals {
+            fraction = fraction * 10.0;
+            let digit = fraction.floor();
+            fraction = fraction - digit;
+            text.push(char::from(b'0' + digit.hi.clamp(0.0, 9.0) as u8));
+        }
+        // Rounded to zero, a negative value prints without its sign
+        let negative = self.hi < 0.0 && text.bytes().any(|byte| matches!(byte, b'1'..=b'9'));
+        f.pad_integral(!negative, "", &text)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let (hi, error) = two_sum(self.hi, other.hi);
+        let (lo, lo_error) = two_sum(self.lo, other.lo);
+        let (hi, error) = quick_two_sum(hi, error + lo);
+        Self::normalised(hi, error + lo_error)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let (hi, error) = two_product(self.hi, other.hi);
+        Self::normalised(hi, error + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Mul<f64> for DoubleDouble {
+    type Output = Self;
+
+    fn mul(self, other: f64) -> Self {
+        let (hi, error) = two_product(self.hi, other);
+        Self::normalised(hi, error + self.lo * other)
+    }
+}
+
+impl Add<f64> for DoubleDouble {
+    type Output = Self;
+
+    fn add(self, other: f64) -> Self {
+        self + Self::from(other)
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = Self;
+
+    // Long division, one f64 quotient digit at a time
+    fn div(self, other: Self) -> Self {
+        let first = self.hi / other.hi;
+        let remainder = self - other * first;
+        let second = remainder.hi / other.hi;
+        let remainder = remainder - other * second;
+        let third = remainder.hi / other.hi;
+        Self::normalised(first, second) + third
+    }
+}
+
+// Complex arithmetic on [re, im] pairs, as in complex.rs
+
+pub fn from_f64(z: [f64; 2]) -> [DoubleDouble; 2] {
+    [z[0].into(), z[1].into()]
+}
+
+pub fn to_f64(z: [DoubleDouble; 2]) -> [f64; 2] {
+    [z[0].to_f64(), z[1].to_f64()]
+}
+
+pub fn add(a: [DoubleDouble; 2], b: [DoubleDouble; 2]) -> [DoubleDouble; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+pub fn sub(a: [DoubleDouble; 2], b: [DoubleDouble; 2]) -> [DoubleDouble; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+pub fn mul(a: [DoubleDouble; 2], b: [DoubleDouble; 2]) -> [DoubleDouble; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+pub fn div(a: [DoubleDouble; 2], b: [DoubleDouble; 2]) -> [DoubleDouble; 2] {
+    let denominator = b[0] * b[0] + b[1] * b[1];
+    [
+        (a[0] * b[0] + a[1] * b[1]) / denominator,
+        (a[1] * b[0] - a[0] * b[1]) / denominator,
+    ]
+}
+
+pub fn scale(a: [DoubleDouble; 2], factor: f64) -> [DoubleDouble; 2] {
+    [a[0] * factor, a[1] * factor]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> DoubleDouble {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn keeps_what_f64_rounds_away() {
+        let tiny = DoubleDouble::from(1e-20);
+        let sum = DoubleDouble::ONE + tiny;
+        assert_eq!(sum.hi, 1.0);
+        assert_eq!(sum.lo, 1e-20);
+        assert_eq!((sum - DoubleDouble::ONE).to_f64(), 1e-20);
+    }
+
+    #[test]
+    fn divides_to_full_precision() {
+        let third = DoubleDouble::ONE / DoubleDouble::from(3.0);
+        let error = third * 3.0 - DoubleDouble::ONE;
+        assert!(error.abs().to_f64() < 4.0 * DoubleDouble::EPSILON);
+        assert_eq!(format!("{third}"), "0.333333333333333333333333333333");
+    }
+
+    #[test]
+    fn parses_and_prints_decimals() {
+        let text = "-1.754877666246692760049508896359";
+        assert_eq!(format!("{}", parse(text)), text);
+        assert_eq!(format!("{:+.3}", parse("2.5e-1")), "+0.250");
+        assert_eq!(format!("{:.2}", parse("0.999")), "1.00");
+        assert_eq!(format!("{:.2}", parse("-0.001")), "0.00");
+        assert!("1.2.3".parse::<DoubleDouble>().is_err());
+    }
+}
@@ -0,0 +1,596 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::JoinHandle,
+};
+
+use crate::scene::SceneError;
+
+// Keyword of the iTXt chunk holding the scene that produced an image, as TOML
+pub const SCENE_KEYWORD: &str = "mandelbrot-explorer scene";
+
+// Offscreen renders use this format so the bytes read back are ready for a PNG
+pub const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+// Linear colour for 16-bit PNGs, and the raw data of fs_data in shader.wgsl
+pub const FLOAT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+// Names of the channels written by fs_data, in order
+const DATA_CHANNELS: [&str; 4] = ["iterations", "magnitude", "distance", "interior"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png8,
+    Png16,
+    // Smooth iteration count, final |z|, distance estimate and interior flag as 32-bit floats
+    ExrData,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 3] = [ImageFormat::Png8, ImageFormat::Png16, ImageFormat::ExrData];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Png8 => "PNG, 8 bits",
+            ImageFormat::Png16 => "PNG, 16 bits",
+            ImageFormat::ExrData => "OpenEXR data",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png8 | ImageFormat::Png16 => "png",
+            ImageFormat::ExrData => "exr",
+        }
+    }
+
+    // Bytes per pixel of the tiles passed to TiledExport::add_tile
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            ImageFormat::Png8 => 4,
+            ImageFormat::Png16 => 8,
+            ImageFormat::ExrData => 16,
+        }
+    }
+}
+
+pub fn create_target(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Export Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+// Copies a texture back to the CPU as tightly packed rows
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Vec<u8> {
+    let width = texture.width();
+    let height = texture.height();
+    let bytes_per_pixel = texture.format().block_size(None).unwrap_or(4);
+    let unpadded_bytes_per_row = width * bytes_per_pixel;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Export Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Export Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+
+    let data = slice.get_mapped_range();
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    for row in data.chunks(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    drop(data);
+    buffer.unmap();
+
+    pixels
+}
+
+// Encodes linear RGBA floats as 16-bit big-endian sRGB, the layout of a 16-bit PNG
+pub fn to_png16(pixels: &[u8]) -> Vec<u8> {
+    let encode = |linear: f32| {
+        let linear = linear.clamp(0.0, 1.0);
+        if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    let mut output = Vec::with_capacity(pixels.len() / 2);
+    for (index, channel) in pixels.chunks_exact(4).enumerate() {
+        let value = f32::from_ne_bytes(channel.try_into().unwrap());
+        // Every fourth channel is alpha, which is not gamma encoded
+        let value = if index % 4 == 3 {
+            value.clamp(0.0, 1.0)
+        } else {
+            encode(value)
+        };
+        output.extend_from_slice(&((value * 65535.0).round() as u16).to_be_bytes());
+    }
+    output
+}
+
+fn png_writer(
+    path: &Path,
+    width: u32,
+    height: u32,
+    depth: png::BitDepth,
+    scene: &str,
+) -> Result<png::Writer<BufWriter<File>>, png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(depth);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    encoder.add_text_chunk("Software".to_string(), "mandelbrot-explorer".to_string())?;
+    encoder.add_itxt_chunk(SCENE_KEYWORD.to_string(), scene.to_string())?;
+    encoder.write_header()
+}
+
+// `scene` is the TOML description of the render, stored so the image can be opened as a scene
+pub fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+    scene: &str,
+) -> Result<(), png::EncodingError> {
+    let mut writer = png_writer(path, width, height, png::BitDepth::Eight, scene)?;
+    writer.write_image_data(pixels)?;
+    writer.finish()
+}
+
+// Height of a row of tiles, which is all of a PNG or OpenEXR image held in memory at once
+const STRIP_HEIGHT: u32 = 256;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Png(png::EncodingError),
+    Exr(exr::error::Error),
+    Ffmpeg(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Png(e) => write!(f, "{}", e),
+            ExportError::Exr(e) => write!(f, "{}", e),
+            ExportError::Ffmpeg(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for ExportError {
+    fn from(e: png::EncodingError) -> Self {
+        ExportError::Png(e)
+    }
+}
+
+impl From<exr::error::Error> for ExportError {
+    fn from(e: exr::error::Error) -> Self {
+        ExportError::Exr(e)
+    }
+}
+
+enum Sink {
+    // PNGs are streamed to disk one row of tiles at a time
+    Png(Box<png::StreamWriter<'static, BufWriter<File>>>),
+    // OpenEXR data is streamed to disk by a thread of its own, see ExrWriter
+    Exr(ExrWriter),
+    // Kept for the caller, such as a frame for a video encoder
+    Raw(Vec<u8>),
+}
+
+// An image too large to render in one go, rendered a tile at a time by the caller. Tiles go
+// left to right, then top to bottom.
+pub struct TiledExport {
+    path: PathBuf,
+    format: ImageFormat,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    sink: Sink,
+    // The row of tiles being filled, as tightly packed rows of the whole image width
+    strip: Vec<u8>,
+    next_tile: u32,
+}
+
+impl TiledExport {
+    pub fn new(
+        path: &Path,
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+        max_tile_width: u32,
+        scene: &str,
+    ) -> Result<Self, ExportError> {
+        let sink = match format {
+            ImageFormat::Png8 | ImageFormat::Png16 => {
+                let depth = match format {
+                    ImageFormat::Png8 => png::BitDepth::Eight,
+                    _ => png::BitDepth::Sixteen,
+                };
+                Sink::Png(Box::new(
+                    png_writer(path, width, height, depth, scene)?.into_stream_writer()?,
+                ))
+            }
+            ImageFormat::ExrData => {
+                Sink::Exr(ExrWriter::spawn(File::create(path)?, width, height, scene))
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            format,
+            width,
+            height,
+            tile_width: max_tile_width.min(width),
+            sink,
+            strip: Vec::new(),
+            next_tile: 0,
+        })
+    }
+
+    // Gathers an RGBA8 image in memory, to be taken with into_pixels. `path` is where it will
+    // end up, for messages.
+    pub fn raw(path: &Path, width: u32, height: u32, max_tile_width: u32) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            format: ImageFormat::Png8,
+            width,
+            height,
+            tile_width: max_tile_width.min(width),
+            sink: Sink::Raw(Vec::new()),
+            strip: Vec::new(),
+            next_tile: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    fn columns(&self) -> u32 {
+        self.width.div_ceil(self.tile_width)
+    }
+
+    fn tile_count(&self) -> u32 {
+        self.columns() * self.height.div_ceil(STRIP_HEIGHT)
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.next_tile as f32 / self.tile_count() as f32
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_tile == self.tile_count()
+    }
+
+    // Pixel offset and size of the tile to render next
+    pub fn next_tile(&self) -> Option<([u32; 2], [u32; 2])> {
+        if self.is_finished() {
+            return None;
+        }
+        let x = self.next_tile % self.columns() * self.tile_width;
+        let y = self.next_tile / self.columns() * STRIP_HEIGHT;
+        Some((
+            [x, y],
+            [
+                self.tile_width.min(self.width - x),
+                STRIP_HEIGHT.min(self.height - y),
+            ],
+        ))
+    }
+
+    // Takes the pixels of the tile given by next_tile, laid out as the format's rows
+    pub fn add_tile(&mut self, pixels: &[u8]) -> Result<(), ExportError> {
+        let Some(([x, _], [width, height])) = self.next_tile() else {
+            return Ok(());
+        };
+        let bytes_per_pixel = self.format.bytes_per_pixel();
+        let row_bytes = self.width as usize * bytes_per_pixel;
+        self.strip.resize(row_bytes * height as usize, 0);
+        for (row, tile_row) in pixels.chunks(width as usize * bytes_per_pixel).enumerate() {
+            let start = row * row_bytes + x as usize * bytes_per_pixel;
+            self.strip[start..start + tile_row.len()].copy_from_slice(tile_row);
+        }
+
+        self.next_tile += 1;
+        if x + width == self.width {
+            match &mut self.sink {
+                Sink::Png(writer) => writer.write_all(&self.strip)?,
+                Sink::Exr(writer) => writer.write_strip(std::mem::take(&mut self.strip))?,
+                Sink::Raw(pixels) => pixels.extend_from_slice(&self.strip),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), ExportError> {
+        match self.sink {
+            Sink::Png(writer) => writer.finish()?,
+            Sink::Exr(writer) => writer.finish()?,
+            Sink::Raw(_) => {}
+        }
+        Ok(())
+    }
+
+    // The image gathered by a raw export, empty for exports written to a file
+    pub fn into_pixels(self) -> Vec<u8> {
+        match self.sink {
+            Sink::Raw(pixels) => pixels,
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Writes OpenEXR data as rows of tiles arrive. The exr crate asks for the blocks of an image in
+// turn, so it runs on a thread that waits for each row while the caller renders the next.
+struct ExrWriter {
+    strips: SyncSender<Vec<u8>>,
+    // None once joined
+    thread: Option<JoinHandle<Result<(), exr::error::Error>>>,
+}
+
+impl ExrWriter {
+    fn spawn(file: File, width: u32, height: u32, scene: &str) -> Self {
+        // One row waiting is enough to keep both sides busy
+        let (strips, received) = mpsc::sync_channel(1);
+        let scene = scene.to_string();
+        let thread = std::thread::spawn(move || {
+            write_exr(BufWriter::new(file), width, height, &scene, received)
+        });
+        Self {
+            strips,
+            thread: Some(thread),
+        }
+    }
+
+    // Takes whole rows of the image, as fs_data pixels
+    fn write_strip(&mut self, strip: Vec<u8>) -> Result<(), ExportError> {
+        if self.strips.send(strip).is_ok() {
+            return Ok(());
+        }
+        // The thread only stops taking rows once writing has failed
+        Self::join(self.thread.take())
+    }
+
+    fn finish(self) -> Result<(), ExportError> {
+        // Closing the channel lets the thread see the end of the image
+        let Self { strips, thread } = self;
+        drop(strips);
+        Self::join(thread)
+    }
+
+    fn join(thread: Option<JoinHandle<Result<(), exr::error::Error>>>) -> Result<(), ExportError> {
+        match thread.map(JoinHandle::join) {
+            Some(Ok(result)) => Ok(result?),
+            Some(Err(_)) => Err(std::io::Error::other("the OpenEXR writer crashed").into()),
+            None => Err(std::io::Error::other("the OpenEXR writer has already stopped").into()),
+        }
+    }
+}
+
+// Writes the four fs_data channels as separate 32-bit float channels, reading the rows from
+// `strips` as they are needed
+fn write_exr(
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    scene: &str,
+    strips: Receiver<Vec<u8>>,
+) -> Result<(), exr::error::Error> {
+    use exr::{
+        block::{self, writer::ChunksWriter, UncompressedBlock},
+        meta::{header::Header, BlockDescription},
+        prelude::*,
+    };
+
+    let width = width as usize;
+    // Channels are stored in alphabetical order, each with the index of its fs_data component
+    let mut channels: Vec<(&str, usize)> = DATA_CHANNELS.iter().copied().zip(0..).collect();
+    channels.sort();
+    let descriptions = channels
+        .iter()
+        .map(|&(name, _)| ChannelDescription::named(name, SampleType::F32))
+        .collect();
+
+    let mut attributes = LayerAttributes::named("mandelbrot");
+    attributes.software_name = Some(Text::from("mandelbrot-explorer"));
+    // EXR text is Latin-1, a scene that does not fit is left out
+    if let Some(scene) = Text::new_or_none(scene) {
+        attributes
+            .other
+            .insert(Text::from(SCENE_KEYWORD), AttributeValue::Text(scene));
+    }
+    let encoding = Encoding::SMALL_LOSSLESS;
+    let header = Header::new(
+        Text::from("mandelbrot"),
+        (width, height as usize),
+        descriptions,
+    )
+    .with_encoding(
+        encoding.compression,
+        BlockDescription::ScanLines,
+        encoding.line_order,
+    )
+    .with_attributes(attributes);
+
+    let row_bytes = width * 16;
+    block::write(
+        file,
+        SmallVec::from_vec(vec![header]),
+        true,
+        |meta, writer| {
+            let mut rows: Vec<u8> = Vec::new();
+            // Row of the image at the start of `rows`
+            let mut first_row = 0;
+            let mut compressor = writer.sequential_blocks_compressor(&meta);
+            for (index, block_index) in block::enumerate_ordered_header_block_indices(&meta.headers)
+            {
+                let top = block_index.pixel_position.y();
+                let bottom = top + block_index.pixel_size.height();
+                rows.drain(..(top - first_row) * row_bytes);
+                first_row = top;
+                while rows.len() < (bottom - first_row) * row_bytes {
+                    let strip = strips.recv().map_err(|_| {
+                        Error::Invalid("the image ended before its last row".into())
+                    })?;
+                    rows.extend_from_slice(&strip);
+                }
+
+                // Each line holds all of one channel, then all of the next
+                let mut data = Vec::with_capacity((bottom - top) * row_bytes);
+                for row in rows[..(bottom - top) * row_bytes].chunks_exact(row_bytes) {
+                    for &(_, component) in &channels {
+                        for pixel in row.chunks_exact(16) {
+                            data.extend_from_slice(&pixel[component * 4..component * 4 + 4]);
+                        }
+                    }
+                }
+                compressor.compress_block(
+                    index,
+                    UncompressedBlock {
+                        index: block_index,
+                        data,
+                    },
+                )?;
+            }
+            Ok(())
+        },
+    )
+}
+
+// Reads back the scene stored by write_png, also accepting it in a plain tEXt chunk
+pub fn read_png_scene(path: &Path) -> Result<String, SceneError> {
+    let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    let reader = decoder
+        .read_info()
+        .map_err(|e| SceneError::Format(e.to_string()))?;
+    let info = reader.info();
+
+    if let Some(chunk) = info
+        .utf8_text
+        .iter()
+        .find(|chunk| chunk.keyword == SCENE_KEYWORD)
+    {
+        return chunk
+            .get_text()
+            .map_err(|e| SceneError::Format(e.to_string()));
+    }
+
+    info.uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == SCENE_KEYWORD)
+        .map(|chunk| chunk.text.clone())
+        .ok_or_else(|| SceneError::Format("the image has no scene stored in it".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_pixel(x: u32, y: u32) -> [f32; 4] {
+        [
+            x as f32,
+            y as f32,
+            (x * y) as f32 / 7.0,
+            ((x + y) % 2) as f32,
+        ]
+    }
+
+    #[test]
+    fn streams_exr_data_a_row_of_tiles_at_a_time() {
+        let path = std::env::temp_dir().join(format!("exr-stream-{}.exr", std::process::id()));
+        let (width, height) = (300, 600);
+        let scene = "version = 1";
+        let mut export =
+            TiledExport::new(&path, ImageFormat::ExrData, width, height, 128, scene).unwrap();
+        while let Some(([x, y], [tile_width, tile_height])) = export.next_tile() {
+            let pixels: Vec<u8> = (y..y + tile_height)
+                .flat_map(|row| (x..x + tile_width).map(move |column| data_pixel(column, row)))
+                .flatten()
+                .flat_map(f32::to_ne_bytes)
+                .collect();
+            export.add_tile(&pixels).unwrap();
+        }
+        export.finish().unwrap();
+
+        use exr::prelude::*;
+        let image = read_first_flat_layer_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let layer = image.layer_data;
+        assert_eq!(layer.size, Vec2(width as usize, height as usize));
+        let stored = layer.attributes.other.get(&Text::from(SCENE_KEYWORD));
+        assert_eq!(stored, Some(&AttributeValue::Text(Text::from(scene))));
+        for (component, name) in DATA_CHANNELS.iter().enumerate() {
+            let channel = layer
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name == **name)
+                .unwrap();
+            for (index, sample) in channel.sample_data.values_as_f32().enumerate() {
+                let (x, y) = (index as u32 % width, index as u32 / width);
+                assert_eq!(sample, data_pixel(x, y)[component], "{name} at {x}, {y}");
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{scene::Colouring, view::View};
+
+/// What is iterated and how the result is coloured, independent of where the view is. The
+/// default is the Mandelbrot set, z = z^2 + c, at 500 iterations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractalParams {
+    /// Iteration limit, points still bounded after it are drawn as inside the set
+    pub iterations: i32,
+    /// z = (|x|^exponent - |y|^exponent, value * x * y) + c, as in the scene's formula
+    pub exponent: f32,
+    pub value: f32,
+    /// Renders the Julia set of `seed`, iterating from each point with c = seed, instead of
+    /// the Mandelbrot set
+    pub julia: bool,
+    pub seed: [f64; 2],
+    pub colouring: Colouring,
+}
+
+impl FractalParams {
+    pub(crate) fn from_view(view: &View, colouring: Colouring) -> Self {
+        Self {
+            iterations: view.iterations,
+            exponent: view.exponent,
+            value: view.value,
+            julia: view.julia,
+            seed: view.seed,
+            colouring,
+        }
+    }
+}
+
+impl Default for FractalParams {
+    fn default() -> Self {
+        Self {
+            iterations: 500,
+            exponent: 2.0,
+            value: 2.0,
+            julia: false,
+            seed: [0.0, 0.0],
+            colouring: Colouring::default(),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use crate::view::View;
+
+// A view has to stay unchanged this long before it is recorded
+const SETTLE_TIME: Duration = Duration::from_millis(600);
+// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 200;
+
+pub struct Entry {
+    pub view: View,
+    pub thumbnail: egui::TextureHandle,
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    current: usize,
+    pending: Option<(View, Instant)>,
+}
+
+impl History {
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    // Called every frame, returns true once `view` has settled and should be recorded
+    pub fn observe(&mut self, view: View, settled: bool) -> bool {
+        self.observe_at(view, settled, Instant::now())
+    }
+
+    fn observe_at(&mut self, view: View, settled: bool, now: Instant) -> bool {
+        if !settled {
+            self.pending = None;
+            return false;
+        }
+
+        match self.pending {
+            Some((pending, since)) if pending == view => {
+                now.duration_since(since) >= SETTLE_TIME
+                    && self.entries.get(self.current).map(|entry| entry.view) != Some(view)
+            }
+            _ => {
+                self.pending = Some((view, now));
+                false
+            }
+        }
+    }
+
+    // Records a view after the current one, discarding anything that could be redone
+    pub fn push(&mut self, view: View, thumbnail: egui::TextureHandle) {
+        if !self.entries.is_empty() {
+            self.entries.truncate(self.current + 1);
+        }
+        self.entries.push(Entry { view, thumbnail });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.current = self.entries.len() - 1;
+    }
+
+    pub fn back(&mut self) -> Option<View> {
+        let index = self.current.checked_sub(1)?;
+        self.go_to(index)
+    }
+
+    pub fn forward(&mut self) -> Option<View> {
+        self.go_to(self.current + 1)
+    }
+
+    pub fn go_to(&mut self, index: usize) -> Option<View> {
+        let view = self.entries.get(index)?.view;
+        self.current = index;
+        Some(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Scene;
+
+    fn view(x: f64) -> View {
+        let mut view = Scene::default().view();
+        view.centre = [x, 0.0];
+        view
+    }
+
+    fn thumbnail() -> egui::TextureHandle {
+        egui::Context::default().load_texture(
+            "thumbnail",
+            egui::ColorImage::new([1, 1], egui::Color32::BLACK),
+            egui::TextureOptions::LINEAR,
+        )
+    }
+
+    fn history(count: usize) -> History {
+        let mut history = History::default();
+        for i in 0..count {
+            history.push(view(i as f64), thumbnail());
+        }
+        history
+    }
+
+    fn centres(history: &History) -> Vec<f64> {
+        history
+            .entries()
+            .iter()
+            .map(|entry| entry.view.centre[0])
+            .collect()
+    }
+
+    #[test]
+    fn records_a_view_once_it_has_settled() {
+        let mut history = History::default();
+        let start = Instant::now();
+        let later = |millis| start + Duration::from_millis(millis);
+        assert!(!history.observe_at(view(1.0), true, start));
+        assert!(!history.observe_at(view(1.0), true, later(599)));
+        assert!(history.observe_at(view(1.0), true, later(600)));
+        history.push(view(1.0), thumbnail());
+        // Already recorded, so not again
+        assert!(!history.observe_at(view(1.0), true, later(2000)));
+    }
+
+    #[test]
+    fn moving_restarts_the_settle_time() {
+        let mut history = History::default();
+        let start = Instant::now();
+        let later = |millis| start + Duration::from_millis(millis);
+        assert!(!history.observe_at(view(1.0), true, start));
+        assert!(!history.observe_at(view(1.0), false, later(300)));
+        assert!(!history.observe_at(view(1.0), true, later(700)));
+        assert!(!history.observe_at(view(1.0), true, later(1200)));
+        assert!(history.observe_at(view(1.0), true, later(1300)));
+        // A different view starts over as well
+        assert!(!history.observe_at(view(2.0), true, later(1400)));
+    }
+
+    #[test]
+    fn goes_back_and_forward() {
+        let mut history = history(3);
+        assert_eq!(history.current(), 2);
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back(), Some(view(1.0)));
+        assert_eq!(history.back(), Some(view(0.0)));
+        assert_eq!(history.back(), None);
+        assert_eq!(history.current(), 0);
+        assert_eq!(history.forward(), Some(view(1.0)));
+        assert_eq!(history.go_to(2), Some(view(2.0)));
+        assert_eq!(history.go_to(3), None);
+        assert_eq!(history.current(), 2);
+    }
+
+    #[test]
+    fn a_new_view_discards_the_forward_history() {
+        let mut history = history(4);
+        history.back();
+        history.back();
+        history.push(view(9.0), thumbnail());
+        assert_eq!(centres(&history), [0.0, 1.0, 9.0]);
+        assert_eq!(history.current(), 2);
+        assert_eq!(history.forward(), None);
+    }
+
+    #[test]
+    fn drops_the_oldest_entries_beyond_the_cap() {
+        let mut history = history(MAX_ENTRIES + 5);
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0].view, view(5.0));
+        assert_eq!(history.current(), MAX_ENTRIES - 1);
+        assert_eq!(history.back(), Some(view((MAX_ENTRIES + 3) as f64)));
+    }
+}
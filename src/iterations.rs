@@ -0,0 +1,208 @@
+// Automatic iteration limits. The curve picks a limit from the zoom depth alone, the adaptive
+// mode then raises it while too many pixels reach the limit without escaping.
+
+use crate::{camera::Camera, readback::Readback};
+
+const MAX_ITERATIONS: f64 = 100_000.0;
+// The adaptive boost grows and shrinks by this factor per counted frame
+const BOOST_STEP: f32 = 1.25;
+const MAX_BOOST: f32 = 16.0;
+// A raise only counts as helping when it resolves at least this share of the unresolved pixels.
+// Pixels inside the set never escape, so the boost stops growing once raising stops helping.
+const MIN_PROGRESS: f32 = 0.02;
+// Raises move the unresolved share gradually, a larger relative jump means a different view
+const NEW_VIEW_CHANGE: f32 = 0.25;
+
+pub struct AutoIterations {
+    pub enabled: bool,
+    pub adaptive: bool,
+    // Limit at the default zoom, plus per_decade for every tenfold zoom raised to power
+    pub base: f32,
+    pub per_decade: f32,
+    pub power: f32,
+    // Share of the window that may reach the limit before the adaptive mode raises it
+    pub unresolved_limit: f32,
+    boost: f32,
+    // Unresolved share just before the last raise
+    last_raise: Option<f32>,
+    // A raise resolved nothing, so no more are made until the view changes
+    stalled: bool,
+}
+
+impl AutoIterations {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            adaptive: false,
+            base: 200.0,
+            per_decade: 100.0,
+            power: 1.5,
+            unresolved_limit: 0.1,
+            boost: 1.0,
+            last_raise: None,
+            stalled: false,
+        }
+    }
+
+    pub fn iterations(&self, axis_range: f64) -> i32 {
+        let decades = (Camera::DEFAULT_RANGE / axis_range).log10().max(0.0);
+        let curve = self.base as f64 + self.per_decade as f64 * decades.powf(self.power as f64);
+        let boost = if self.adaptive { self.boost } else { 1.0 };
+        (curve * boost as f64).clamp(1.0, MAX_ITERATIONS) as i32
+    }
+
+    // Adjusts the boost from the share of pixels that reached the limit in a settled view
+    pub fn observe(&mut self, unresolved: f32, settled: bool) {
+        if !settled {
+            return;
+        }
+        let jumped = |last: f32| (unresolved - last).abs() > last * NEW_VIEW_CHANGE;
+        if self.last_raise.is_some_and(jumped) {
+            self.last_raise = None;
+            self.stalled = false;
+        }
+        if unresolved > self.unresolved_limit {
+            match self.last_raise {
+                _ if self.stalled => {}
+                // The rest is inside the set, so the last raise only cost time
+                Some(last) if unresolved >= last * (1.0 - MIN_PROGRESS) => {
+                    self.boost = (self.boost / BOOST_STEP).max(1.0);
+                    self.stalled = true;
+                }
+                _ if self.boost < MAX_BOOST => {
+                    self.boost = (self.boost * BOOST_STEP).min(MAX_BOOST);
+                    self.last_raise = Some(unresolved);
+                }
+                _ => {}
+            }
+        } else if unresolved < self.unresolved_limit / 2.0 {
+            self.boost = (self.boost / BOOST_STEP).max(1.0);
+            self.last_raise = None;
+            self.stalled = false;
+        }
+    }
+}
+
+// Counts the pixels of the window that reach the iteration limit, see fs_main in shader.wgsl
+pub struct UnresolvedCounter {
+    buffer: wgpu::Buffer,
+    readback: Readback,
+}
+
+const COUNTER_BYTES: u64 = std::mem::size_of::<u32>() as u64;
+
+impl UnresolvedCounter {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Unresolved Counter"),
+            size: COUNTER_BYTES,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            readback: Readback::new(device, COUNTER_BYTES, "Unresolved Counter Readback"),
+        }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn ready(&self) -> bool {
+        self.readback.ready()
+    }
+
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.buffer, 0, None);
+    }
+
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.readback.copy_from(encoder, &self.buffer);
+    }
+
+    // Call after the encoder passed to end has been submitted
+    pub fn request(&mut self) {
+        self.readback.request();
+    }
+
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<u32> {
+        let data = self.readback.poll(device)?;
+        Some(bytemuck::pod_read_unaligned(
+            &data[..COUNTER_BYTES as usize],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observe_all(auto: &mut AutoIterations, shares: &[f32]) {
+        for &share in shares {
+            auto.observe(share, true);
+        }
+    }
+
+    #[test]
+    fn raises_while_raising_helps() {
+        let mut auto = AutoIterations::new();
+        observe_all(&mut auto, &[0.5, 0.45, 0.4]);
+        assert_eq!(auto.boost, BOOST_STEP.powi(3));
+        auto.adaptive = true;
+        let raised = auto.iterations(Camera::DEFAULT_RANGE);
+        assert_eq!(raised, (auto.base * BOOST_STEP.powi(3)) as i32);
+    }
+
+    #[test]
+    fn undoes_a_raise_that_resolves_nothing() {
+        let mut auto = AutoIterations::new();
+        observe_all(&mut auto, &[0.5, 0.4, 0.4]);
+        assert_eq!(auto.boost, BOOST_STEP);
+        // Undoing it leaves a few more pixels unresolved, which changes nothing
+        observe_all(&mut auto, &[0.405; 20]);
+        assert_eq!(auto.boost, BOOST_STEP);
+    }
+
+    #[test]
+    fn never_passes_the_maximum() {
+        let mut auto = AutoIterations::new();
+        let shares: Vec<f32> = (0..40).map(|i| 0.9 * 0.95f32.powi(i)).collect();
+        observe_all(&mut auto, &shares);
+        assert_eq!(auto.boost, MAX_BOOST);
+    }
+
+    #[test]
+    fn moving_keeps_the_baseline() {
+        let mut auto = AutoIterations::new();
+        observe_all(&mut auto, &[0.5, 0.4, 0.4]);
+        let boost = auto.boost;
+        for _ in 0..10 {
+            auto.observe(0.05, false);
+            auto.observe(0.4, true);
+        }
+        assert_eq!(auto.boost, boost);
+    }
+
+    #[test]
+    fn judges_a_new_view_afresh() {
+        let mut auto = AutoIterations::new();
+        observe_all(&mut auto, &[0.5, 0.5]);
+        assert_eq!(auto.boost, 1.0);
+        observe_all(&mut auto, &[0.8, 0.7]);
+        assert_eq!(auto.boost, BOOST_STEP * BOOST_STEP);
+    }
+
+    #[test]
+    fn decays_once_the_view_resolves() {
+        let mut auto = AutoIterations::new();
+        observe_all(&mut auto, &[0.5, 0.45, 0.4]);
+        observe_all(&mut auto, &[0.01; 3]);
+        assert_eq!(auto.boost, 1.0);
+        // Shares between half the limit and the limit hold the boost
+        observe_all(&mut auto, &[0.5, 0.08, 0.08]);
+        assert_eq!(auto.boost, BOOST_STEP);
+    }
+}
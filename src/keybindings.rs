@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+pub const FILE_NAME: &str = "keybindings.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    IncreaseIterations,
+    DecreaseIterations,
+    ResetView,
+    HistoryBack,
+    HistoryForward,
+    Screenshot,
+    ToggleUi,
+    ToggleOverlay,
+    FindNucleus,
+    FindMisiurewicz,
+    Quit,
+}
+
+// Keys are written with winit's VirtualKeyCode names, e.g. "W", "Up", "Equals" or "F12"
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub pan_up: Vec<VirtualKeyCode>,
+    pub pan_down: Vec<VirtualKeyCode>,
+    pub pan_left: Vec<VirtualKeyCode>,
+    pub pan_right: Vec<VirtualKeyCode>,
+    pub zoom_in: Vec<VirtualKeyCode>,
+    pub zoom_out: Vec<VirtualKeyCode>,
+    pub increase_iterations: Vec<VirtualKeyCode>,
+    pub decrease_iterations: Vec<VirtualKeyCode>,
+    pub reset_view: Vec<VirtualKeyCode>,
+    pub history_back: Vec<VirtualKeyCode>,
+    pub history_forward: Vec<VirtualKeyCode>,
+    pub screenshot: Vec<VirtualKeyCode>,
+    pub toggle_ui: Vec<VirtualKeyCode>,
+    pub toggle_overlay: Vec<VirtualKeyCode>,
+    pub find_nucleus: Vec<VirtualKeyCode>,
+    pub find_misiurewicz: Vec<VirtualKeyCode>,
+    pub quit: Vec<VirtualKeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        Self {
+            pan_up: vec![W, Up],
+            pan_down: vec![S, Down],
+            pan_left: vec![A, Left],
+            pan_right: vec![D, Right],
+            zoom_in: vec![Equals, Plus, NumpadAdd],
+            zoom_out: vec![Minus, NumpadSubtract],
+            increase_iterations: vec![PageUp],
+            decrease_iterations: vec![PageDown],
+            reset_view: vec![R, Home],
+            history_back: vec![LBracket, NavigateBackward],
+            history_forward: vec![RBracket, NavigateForward],
+            screenshot: vec![P, F12],
+            toggle_ui: vec![H, F1],
+            toggle_overlay: vec![I, F3],
+            find_nucleus: vec![N],
+            find_misiurewicz: vec![M],
+            quit: vec![Escape],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn load() -> Self {
+        crate::config::load(FILE_NAME)
+    }
+
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        [
+            (Action::PanUp, &self.pan_up),
+            (Action::PanDown, &self.pan_down),
+            (Action::PanLeft, &self.pan_left),
+            (Action::PanRight, &self.pan_right),
+            (Action::ZoomIn, &self.zoom_in),
+            (Action::ZoomOut, &self.zoom_out),
+            (Action::IncreaseIterations, &self.increase_iterations),
+            (Action::DecreaseIterations, &self.decrease_iterations),
+            (Action::ResetView, &self.reset_view),
+            (Action::HistoryBack, &self.history_back),
+            (Action::HistoryForward, &self.history_forward),
+            (Action::Screenshot, &self.screenshot),
+            (Action::ToggleUi, &self.toggle_ui),
+            (Action::ToggleOverlay, &self.toggle_overlay),
+            (Action::FindNucleus, &self.find_nucleus),
+            (Action::FindMisiurewicz, &self.find_misiurewicz),
+            (Action::Quit, &self.quit),
+        ]
+        .into_iter()
+        .find(|(_, keys)| keys.contains(&key))
+        .map(|(action, _)| action)
+    }
+}
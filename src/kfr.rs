@@ -0,0 +1,331 @@
+// Kalles Fraktaler location files: one "Key: value" pair per line, e.g.
+//
+//     Re: -0.75
+//     Im: 0
+//     Zoom: 1
+//     Iterations: 200
+
+use crate::scene::{parse_precise, Imported, Scene, SceneError};
+
+// At zoom 1 Kalles Fraktaler shows a radius of 2 vertically, so the height is 4 / zoom
+const UNZOOMED_HEIGHT: f64 = 4.0;
+
+// Fields that describe Kalles Fraktaler's own colouring and are dropped without a warning
+// being issued for each one individually
+const COLOURING_FIELDS: &[&str] = &[
+    "Colors",
+    "InteriorColor",
+    "Smooth",
+    "MultiColor",
+    "BlendMC",
+    "MultiColors",
+    "IterDiv",
+    "SmoothMethod",
+    "ColorMethod",
+    "Differences",
+    "ColorOffset",
+    "ColorPhaseStrength",
+    "Slopes",
+    "SlopePower",
+    "SlopeRatio",
+    "SlopeAngle",
+    "TextureEnabled",
+    "TextureFile",
+    "TextureMerge",
+    "TexturePower",
+    "TextureRatio",
+];
+
+// Formula parameters that leave the Mandelbrot set unchanged at these values, written to
+// every file
+const NEUTRAL_FIELDS: &[(&str, f64)] = &[
+    ("real", 1.0),
+    ("imag", 1.0),
+    ("SeedR", 0.0),
+    ("SeedI", 0.0),
+    ("FactorAR", 1.0),
+    ("FactorAI", 0.0),
+];
+
+pub fn read(contents: &str) -> Result<Imported, SceneError> {
+    let mut scene = Scene::default();
+    let mut warnings = Vec::new();
+    let mut re = None;
+    let mut im = None;
+    let mut ignored_colouring = Vec::new();
+
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "Re" => re = parse_precise("Re", value, &mut warnings),
+            "Im" => im = parse_precise("Im", value, &mut warnings),
+            "Zoom" => match value.parse::<f64>() {
+                Ok(zoom) if zoom > 0.0 && zoom.is_finite() => {
+                    scene.view.axis_range = UNZOOMED_HEIGHT / zoom;
+                }
+                _ => warnings.push(format!("Zoom {} is out of range, kept the default", value)),
+            },
+            "Iterations" => match value.parse::<i64>() {
+                Ok(iterations) => {
+                    let clamped = iterations.clamp(1, i32::MAX as i64);
+                    if clamped != iterations {
+                        warnings.push(format!(
+                            "Iterations {} is out of range, used {}",
+                            value, clamped
+                        ));
+                    }
+                    scene.render.iterations = clamped as i32;
+                }
+                Err(_) => warnings.push(format!("Iterations {} is not a number", value)),
+            },
+            "FractalType" if value != "0" => warnings.push(format!(
+                "FractalType {} is not supported, rendered as the Mandelbrot set",
+                value
+            )),
+            "Power" if value.parse::<f64>() != Ok(2.0) => warnings.push(format!(
+                "Power {} is not supported, rendered with power 2",
+                value
+            )),
+            "Rotate" if value.parse::<f64>().unwrap_or(0.0) != 0.0 => {
+                warnings.push(format!("Rotate {} is ignored, views cannot rotate", value));
+            }
+            "Ratio" if value.parse::<f64>().unwrap_or(360.0) != 360.0 => {
+                warnings.push(format!(
+                    "Ratio {} is ignored, views cannot be stretched",
+                    value
+                ));
+            }
+            "FractalType" | "Power" | "Rotate" | "Ratio" => {}
+            // The period Kalles Fraktaler found for the view, which nothing here needs
+            "Period" => {}
+            key if NEUTRAL_FIELDS
+                .iter()
+                .any(|&(name, neutral)| name == key && value.parse() == Ok(neutral)) => {}
+            key if COLOURING_FIELDS.contains(&key) => ignored_colouring.push(key.to_string()),
+            key => warnings.push(format!("{} is not supported and was ignored", key)),
+        }
+    }
+
+    if !ignored_colouring.is_empty() {
+        warnings.push(format!(
+            "Kalles Fraktaler colouring is not supported, ignored {}",
+            ignored_colouring.join(", ")
+        ));
+    }
+
+    match (re, im) {
+        (Some(re), Some(im)) => scene.view.centre = [re, im],
+        _ => return Err(SceneError::Format("missing or invalid Re/Im".to_string())),
+    }
+
+    Ok(Imported {
+        scene: scene.validate()?,
+        warnings,
+    })
+}
+
+pub fn write(scene: &Scene) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    if !scene.formula.is_standard() {
+        warnings.push(format!(
+            "exponent {} and value {} have no Kalles Fraktaler equivalent, written as the Mandelbrot set",
+            scene.formula.exponent, scene.formula.value
+        ));
+    }
+    if scene.formula.julia {
+        warnings.push(
+            "Julia sets are not written to .kfr files, written as the Mandelbrot set".to_string(),
+        );
+    }
+    warnings.push("colouring is not written to .kfr files".to_string());
+
+    let [re, im] = scene.view.centre;
+    let lines = [
+        format!("Re: {}", re),
+        format!("Im: {}", im),
+        format!("Zoom: {:E}", UNZOOMED_HEIGHT / scene.view.axis_range),
+        format!("Iterations: {}", scene.render.iterations),
+        "FractalType: 0".to_string(),
+        "Power: 2".to_string(),
+        "Rotate: 0".to_string(),
+        "Ratio: 360".to_string(),
+    ];
+
+    // Kalles Fraktaler writes Windows line endings
+    (lines.join("\r\n") + "\r\n", warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene(centre: [f64; 2], axis_range: f64, iterations: i32) -> Scene {
+        let mut scene = Scene::default();
+        scene.view.centre = centre;
+        scene.view.axis_range = axis_range;
+        scene.render.iterations = iterations;
+        scene
+    }
+
+    fn has_warning(warnings: &[String], expected: &str) -> bool {
+        warnings.iter().any(|warning| warning.contains(expected))
+    }
+
+    #[test]
+    fn round_trips_through_write() {
+        for original in [
+            Scene::default(),
+            scene(
+                [-1.769383179195515, 0.0042368479187367],
+                3.4e-290,
+                1_000_000,
+            ),
+            scene([0.25, -1e-9], 1e5, 1),
+        ] {
+            let (contents, warnings) = write(&original);
+            assert_eq!(warnings, ["colouring is not written to .kfr files"]);
+            let imported = read(&contents).unwrap();
+            assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+            let read = imported.scene;
+            assert_eq!(read.view.centre, original.view.centre);
+            let error = (read.view.axis_range / original.view.axis_range - 1.0).abs();
+            assert!(
+                error < 1e-15,
+                "{} became {}",
+                original.view.axis_range,
+                read.view.axis_range
+            );
+            assert_eq!(read.render.iterations, original.render.iterations);
+        }
+    }
+
+    #[test]
+    fn writes_a_julia_set_as_the_mandelbrot_set() {
+        let mut original = Scene::default();
+        original.formula.julia = true;
+        original.formula.seed = [-0.8, 0.156];
+        let (contents, warnings) = write(&original);
+        assert!(has_warning(&warnings, "Julia sets are not written"));
+        assert!(!read(&contents).unwrap().scene.formula.julia);
+    }
+
+    #[test]
+    fn writes_a_custom_formula_as_the_mandelbrot_set() {
+        let mut original = Scene::default();
+        original.formula.exponent = 3.0;
+        let (contents, warnings) = write(&original);
+        assert!(has_warning(&warnings, "exponent 3 and value 2"));
+        assert!(contents.contains("Power: 2\r\n"));
+        assert!(read(&contents).unwrap().scene.formula.is_standard());
+    }
+
+    #[test]
+    fn reads_a_kalles_fraktaler_file() {
+        let imported = read(include_str!("../testdata/seahorse.kfr")).unwrap();
+        let scene = imported.scene;
+        let centre = [
+            "-0.743643887037158704752191506114774",
+            "0.131825904205311970493132056385139",
+        ];
+        assert_eq!(
+            scene.view.centre,
+            centre.map(|text| text.parse::<f64>().unwrap())
+        );
+        assert_eq!(
+            scene.view.axis_range,
+            UNZOOMED_HEIGHT / 2.7144176165949066E12
+        );
+        assert_eq!(scene.render.iterations, 20000);
+
+        // Only the precision lost and the colouring, the neutral formula fields stay quiet
+        let warnings = imported.warnings;
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert!(has_warning(&warnings, "Re has 33 significant digits"));
+        assert!(has_warning(&warnings, "Im has 33 significant digits"));
+        assert!(has_warning(
+            &warnings,
+            "colouring is not supported, ignored IterDiv"
+        ));
+    }
+
+    #[test]
+    fn warns_about_what_does_not_carry_over() {
+        let contents = "Re: 0\nIm: 0\nZoom: 1\nIterations: 100\nPower: 3\nFractalType: 4\n\
+                        Rotate: 30\nRatio: 200\nSeedR: 0.5\nHybrid: 1\n";
+        let warnings = read(contents).unwrap().warnings;
+        for expected in [
+            "Power 3 is not supported",
+            "FractalType 4 is not supported",
+            "Rotate 30 is ignored",
+            "Ratio 200 is ignored",
+            "SeedR is not supported",
+            "Hybrid is not supported",
+        ] {
+            assert!(
+                has_warning(&warnings, expected),
+                "{}: {:?}",
+                expected,
+                warnings
+            );
+        }
+        assert_eq!(warnings.len(), 6);
+    }
+
+    #[test]
+    fn keeps_defaults_for_invalid_values() {
+        let imported = read("Re: 0\nIm: 0\nZoom: 0\nIterations: many\n").unwrap();
+        assert_eq!(
+            imported.scene.view.axis_range,
+            Scene::default().view.axis_range
+        );
+        assert_eq!(
+            imported.scene.render.iterations,
+            Scene::default().render.iterations
+        );
+        assert!(has_warning(&imported.warnings, "Zoom 0 is out of range"));
+        assert!(has_warning(
+            &imported.warnings,
+            "Iterations many is not a number"
+        ));
+
+        let imported = read("Re: 0\nIm: 0\nIterations: -4\n").unwrap();
+        assert_eq!(imported.scene.render.iterations, 1);
+        assert!(has_warning(
+            &imported.warnings,
+            "Iterations -4 is out of range, used 1"
+        ));
+        let imported = read("Re: 0\nIm: 0\nIterations: 0\n").unwrap();
+        assert_eq!(imported.scene.render.iterations, 1);
+        assert_eq!(imported.warnings.len(), 1);
+    }
+
+    #[test]
+    fn reads_the_power_as_a_number() {
+        for power in ["2", "2.0", " 2 ", "2e0"] {
+            let contents = format!("Re: 0\nIm: 0\nPower: {}\n", power);
+            let warnings = read(&contents).unwrap().warnings;
+            assert!(warnings.is_empty(), "{:?}: {:?}", power, warnings);
+        }
+        let warnings = read("Re: 0\nIm: 0\nPower: two\n").unwrap().warnings;
+        assert!(has_warning(&warnings, "Power two is not supported"));
+    }
+
+    #[test]
+    fn needs_a_centre() {
+        assert!(matches!(
+            read("Im: 0\nZoom: 1\n"),
+            Err(SceneError::Format(_))
+        ));
+        assert!(matches!(
+            read("Re: west\nIm: 0\n"),
+            Err(SceneError::Format(_))
+        ));
+        assert!(matches!(
+            read("Re: nan\nIm: 0\n"),
+            Err(SceneError::Format(_))
+        ));
+    }
+}
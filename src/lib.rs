@@ -2,12 +2,51 @@
 // rename shader_uniform to uniform and wrap mouse_position, resolution, zoom, etc. in a struct
 // tweak mouse position to be in the range of the fractal (eg. -2.0 to 2.0)
 // change sensitivity of mouse based on zoom level
-// or maybe consider switching to WASD controls
 // look at restructuring the code to be more modular
 // add egui
-// write a struct for mandelbrot/fractal parameters
-// let these be changed by egui
+mod animation;
+mod bookmarks;
+mod camera;
+mod complex;
+mod config;
+mod double_double;
+mod export;
+mod fractal;
+mod history;
+mod iterations;
+mod keybindings;
+mod kfr;
+mod misiurewicz;
+mod nucleus;
+mod orbit;
+mod rays;
+mod readback;
+mod renderer;
+mod scene;
+mod share;
+mod timing;
 mod ui;
+mod video;
+mod view;
+mod xpf;
+
+use std::{
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bookmarks::{Bookmark, Bookmarks};
+use history::History;
+use keybindings::{Action, KeyBindings};
+use renderer::ShaderUniform;
+use scene::Scene;
+use view::View;
+
+pub use camera::Viewport;
+pub use fractal::FractalParams;
+pub use renderer::{RenderError, Renderer, SetupError};
+pub use scene::{Colouring, Palette};
 
 use wgpu::util::DeviceExt;
 use winit::{
@@ -16,104 +55,6 @@ use winit::{
     window::{Window, WindowBuilder},
 };
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Mouse {
-    //            align(16) size(32)
-    x: f32,        // offset(0)  align(4)  size(4)
-    y: f32,        // offset(4)  align(4)  size(4)
-    drag: i32,     // offset(8)  align(4)  size(4)
-    px: f32,       // offset(12) align(4)  size(4)
-    py: f32,       // offset(16) align(4)  size(4)
-    centre_x: f32, // offset(20) align(4)  size(4)
-    centre_y: f32, // offset(24) align(4)  size(4)
-    _pad: u32,     // offset(28)           size(4)
-}
-
-impl Mouse {
-    fn new() -> Self {
-        Self {
-            x: 0.0,
-            y: 0.0,
-            drag: -1,
-            px: 0.0,
-            py: 0.0,
-            centre_x: -0.765,
-            centre_y: 0.0,
-            _pad: 0,
-        }
-    }
-
-    fn set_drag(&mut self) {
-        self.drag = 1;
-    }
-
-    fn unset_drag(&mut self) {
-        self.drag = 0;
-    }
-
-    fn update_position(&mut self, x: f32, y: f32) {
-        self.px = self.x;
-        self.py = self.y;
-        self.x = -x;
-        self.y = -y;
-    }
-
-    fn drag_mouse(&mut self, resolution: [f32; 2], axis_range: f32) {
-        if self.drag == 1 {
-            let ratio = resolution[0] / resolution[1];
-            let x = self.x - self.px;
-            let y = self.y - self.py;
-            let x = axis_range * x / resolution[0] * ratio;
-            let y = axis_range * y / resolution[1];
-            self.centre_x += x;
-            self.centre_y += y;
-        }
-    }
-}
-
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct ShaderUniform {
-    //            align(16) size(64)
-    resolution: [f32; 2], // offset(0)  align(8)  size(8)
-    iterations: i32,      // offset(8)  align(4)  size(4)
-    value: f32,           // offset(12) align(4)  size(4)
-    mouse: Mouse,         // offset(16) align(16) size(32)
-    axis_range: f32,      // offset(48) align(4)  size(4)
-    exponent: f32,        // offset(48) align(4)  size(4)
-    _pad: [u32; 2],
-}
-
-impl ShaderUniform {
-    fn new() -> Self {
-        Self {
-            resolution: [0.0, 0.0],
-            iterations: 100,
-            value: 2.0,
-            mouse: Mouse::new(),
-            axis_range: 2.0,
-            exponent: 2.0,
-            _pad: [0; 2],
-        }
-    }
-
-    fn update_resolution(&mut self, width: f32, height: f32) {
-        self.resolution = [width, height];
-    }
-
-    fn update_iterations(&mut self, iterations: i32) {
-        self.iterations = iterations;
-    }
-
-    fn update_value(&mut self, value: f32) {
-        self.value = value;
-    }
-
-    fn update_exponent(&mut self, exponent: f32) {
-        self.exponent = exponent;
-    }
-}
 struct UiWrapper {
     ctx: egui::Context,
     wgpu_ctx: egui_wgpu::Renderer,
@@ -121,94 +62,80 @@ struct UiWrapper {
     interface: ui::Interface,
 }
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    colour: [f32; 3],
-}
-
-impl Vertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
-
-    fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &Self::ATTRIBS,
-        }
-    }
-}
-
-const VERTICES: &[Vertex] = &[
-    Vertex {
-        position: [-1.0, 1.0, 0.0],
-        colour: [1.0, 0.0, 0.0],
-    },
-    Vertex {
-        position: [-1.0, -1.0, 0.0],
-        colour: [0.0, 1.0, 0.0],
-    },
-    Vertex {
-        position: [1.0, -1.0, 0.0],
-        colour: [0.0, 0.0, 1.0],
-    },
-    Vertex {
-        position: [1.0, 1.0, 0.0],
-        colour: [0.3, 0.4, 0.3],
-    },
-];
-
-const INDICES: &[u16] = &[0, 1, 3, 1, 2, 3];
+// Trackpads report pixel deltas, this many pixels count as one line
+const PIXELS_PER_LINE: f32 = 20.0;
+// Keyboard panning moves the view by this fraction of the screen height per key press
+const PAN_STEP: f64 = 0.1;
+// Keyboard zoom moves by this many scroll lines per key press
+const ZOOM_LINES: f64 = 1.0;
+// Iteration keys scale the iteration count by this factor
+const ITERATION_STEP: f32 = 1.25;
+// Time spent rendering export tiles per frame, the window stays responsive in between
+const EXPORT_TIME_PER_FRAME: Duration = Duration::from_millis(30);
+// Weight of the newest frame in the smoothed frame times
+const FRAME_TIME_SMOOTHING: f64 = 0.1;
+// Size in pixels of the thumbnails shown in the history list
+const THUMBNAIL_SIZE: [u32; 2] = [96, 64];
+// Box selections smaller than this many pixels on a side are treated as a click
+const MIN_SELECTION: f32 = 4.0;
 
 struct State {
     surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
+    renderer: Renderer,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     window: Window,
+    // Colours the window from data_bind_group's texture
     render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
+    data_view: wgpu::TextureView,
+    data_bind_group: wgpu::BindGroup,
+    // What the data pass last rendered, it is skipped while this still matches
+    last_data: Option<ShaderUniform>,
     shader_uniform: ShaderUniform,
     shader_buffer: wgpu::Buffer,
     shader_bind_group: wgpu::BindGroup,
     ui_wrapper: UiWrapper,
+    camera: camera::Camera,
+    last_update: Instant,
+    last_cursor_move: Instant,
+    modifiers: ModifiersState,
+    // Corners of the box zoom selection in window pixels, while one is being dragged
+    selection: Option<[[f32; 2]; 2]>,
+    keybindings: KeyBindings,
+    history: History,
+    bookmarks: Bookmarks,
+    clipboard: egui_winit::clipboard::Clipboard,
+    adapter_name: String,
+    gpu_timer: Option<timing::GpuTimer>,
+    unresolved: iterations::UnresolvedCounter,
+    // Image export in progress, rendering the view captured in export_uniform
+    tiled_export: Option<export::TiledExport>,
+    export_uniform: ShaderUniform,
+    // Animation being written a frame at a time through tiled_export
+    recording: Option<animation::Recording>,
+    // Angle estimate running on its own thread, the samples can take seconds near the set
+    angle_estimation: Option<JoinHandle<Vec<(String, rays::Angle)>>>,
+    // Smoothed seconds between frames, and for the fractal pass on the GPU when it can be timed
+    frame_time: f64,
+    gpu_time: Option<f64>,
 }
 
 impl State {
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window) -> Result<Self, SetupError> {
         let size = window.inner_size();
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             dx12_shader_compiler: Default::default(),
         });
 
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let surface = unsafe { instance.create_surface(&window) }?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let (adapter, renderer) = Renderer::request(&instance, Some(&surface)).await?;
+        let device = renderer.device();
+
+        let adapter_info = adapter.get_info();
+        let adapter_name = format!("{} ({:?})", adapter_info.name, adapter_info.backend);
+        let gpu_timer = timing::GpuTimer::new(device, renderer.queue());
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
@@ -216,7 +143,8 @@ impl State {
             .iter()
             .copied()
             .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+            .or(surface_caps.formats.first().copied())
+            .ok_or(SetupError::UnsupportedSurface)?;
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -227,12 +155,7 @@ impl State {
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
-        surface.configure(&device, &config);
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
+        surface.configure(device, &config);
 
         let mut shader_uniform = ShaderUniform::new();
         shader_uniform.update_resolution(
@@ -240,95 +163,30 @@ impl State {
             window.inner_size().height as f32,
         );
 
+        let camera = camera::Camera::new(
+            window.inner_size().width as f32,
+            window.inner_size().height as f32,
+        );
+
         let shader_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("shader uniform"),
             contents: bytemuck::cast_slice(&[shader_uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let shader_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-                label: Some("shader_bind_group_layout"),
-            });
-
-        let shader_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &shader_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: shader_buffer.as_entire_binding(),
-            }],
-            label: Some("shader_bind_group"),
-        });
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&shader_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
-            usage: wgpu::BufferUsages::INDEX,
-        });
+        let unresolved = iterations::UnresolvedCounter::new(device);
+        let shader_bind_group =
+            renderer.create_uniform_bind_group(&shader_buffer, unresolved.buffer());
 
-        let num_indices = INDICES.len() as u32;
+        // The window iterates into a float target and colours from it in a second pass, so
+        // changing only the colouring skips the iteration
+        let render_pipeline = renderer.create_colour_pipeline(config.format);
+        let (data_view, data_bind_group) = renderer.create_data_target(size.width, size.height);
 
         let egui_ctx = egui::Context::default();
-        let wgpu_ctx = egui_wgpu::Renderer::new(&device, surface_format, None, 1);
+        let wgpu_ctx = egui_wgpu::Renderer::new(device, surface_format, None, 1);
         let winit_ctx = egui_winit::State::new(&window);
+        let clipboard = egui_winit::clipboard::Clipboard::new(&window);
 
         let ui_wrapper = UiWrapper {
             ctx: egui_ctx,
@@ -337,22 +195,39 @@ impl State {
             interface: ui::Interface::new(),
         };
 
-        Self {
+        Ok(Self {
             window,
             surface,
-            device,
-            queue,
+            renderer,
             config,
             size,
             render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
+            data_view,
+            data_bind_group,
+            last_data: None,
             shader_uniform,
             shader_buffer,
             shader_bind_group,
             ui_wrapper,
-        }
+            camera,
+            last_update: Instant::now(),
+            last_cursor_move: Instant::now(),
+            modifiers: ModifiersState::empty(),
+            selection: None,
+            keybindings: KeyBindings::load(),
+            history: History::default(),
+            bookmarks: Bookmarks::load(),
+            clipboard,
+            adapter_name,
+            gpu_timer,
+            unresolved,
+            tiled_export: None,
+            export_uniform: shader_uniform,
+            recording: None,
+            angle_estimation: None,
+            frame_time: 0.0,
+            gpu_time: None,
+        })
     }
 
     pub fn window(&self) -> &Window {
@@ -366,25 +241,86 @@ impl State {
             self.config.height = new_size.height;
             self.shader_uniform
                 .update_resolution(new_size.width as f32, new_size.height as f32);
-            self.surface.configure(&self.device, &self.config);
+            self.camera
+                .resize(new_size.width as f32, new_size.height as f32);
+            self.surface.configure(self.renderer.device(), &self.config);
+            (self.data_view, self.data_bind_group) = self
+                .renderer
+                .create_data_target(new_size.width, new_size.height);
+            self.last_data = None;
         }
     }
 
     fn input(&mut self, event: &WindowEvent) -> bool {
-        self.ui_wrapper
+        let response = self
+            .ui_wrapper
             .winit_ctx
-            .on_event(&self.ui_wrapper.ctx, event)
-            .consumed;
+            .on_event(&self.ui_wrapper.ctx, event);
+        // Scrolling a list or dragging a slider should not move the fractal underneath as well
+        let over_ui = response.consumed || self.ui_wrapper.ctx.wants_pointer_input();
 
         match event {
             WindowEvent::CursorMoved { position, .. } => {
                 self.shader_uniform
                     .mouse
                     .update_position(position.x as f32, position.y as f32);
-                self.shader_uniform.mouse.drag_mouse(
-                    self.shader_uniform.resolution,
-                    self.shader_uniform.axis_range,
-                );
+                let mouse = &self.shader_uniform.mouse;
+                // mouse.x and mouse.y hold the negated cursor position
+                let (dx, dy) = (mouse.px - mouse.x, mouse.py - mouse.y);
+                let now = Instant::now();
+                let dt = now.duration_since(self.last_cursor_move).as_secs_f64();
+                self.last_cursor_move = now;
+                self.camera.drag(dx as f64, dy as f64, dt);
+                if let Some([_, end]) = &mut self.selection {
+                    *end = self.shader_uniform.mouse.position();
+                }
+                true
+            }
+
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+                false
+            }
+
+            // Releases still reach the fractal, so a drag started on it always ends
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                ..
+            }
+            | WindowEvent::MouseWheel { .. }
+                if over_ui =>
+            {
+                true
+            }
+
+            WindowEvent::MouseInput {
+                button,
+                state: ElementState::Pressed,
+                ..
+            } if *button == MouseButton::Right
+                || (*button == MouseButton::Left && self.modifiers.shift()) =>
+            {
+                let position = self.shader_uniform.mouse.position();
+                self.selection = Some([position, position]);
+                true
+            }
+
+            WindowEvent::MouseInput {
+                button: MouseButton::Left | MouseButton::Right,
+                state: ElementState::Released,
+                ..
+            } if self.selection.is_some() => {
+                self.finish_selection();
+                true
+            }
+
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Pressed,
+                ..
+            } if self.modifiers.alt() => {
+                let [x, y] = self.shader_uniform.mouse.position();
+                self.estimate_angles(self.camera.get_coordinate(x as f64, y as f64));
                 true
             }
 
@@ -394,6 +330,7 @@ impl State {
                 ..
             } => {
                 self.shader_uniform.mouse.set_drag();
+                self.camera.start_drag();
                 true
             }
 
@@ -403,25 +340,760 @@ impl State {
                 ..
             } => {
                 self.shader_uniform.mouse.unset_drag();
+                self.camera
+                    .end_drag(self.last_cursor_move.elapsed().as_secs_f64());
                 true
             }
 
-            WindowEvent::MouseWheel { delta, .. } => match delta {
-                MouseScrollDelta::LineDelta(_, y) => {
-                    if *y < 0.0 {
-                        self.shader_uniform.axis_range *= 1.05
-                    } else {
-                        self.shader_uniform.axis_range *= 0.95
-                    }
+            WindowEvent::MouseInput {
+                button: MouseButton::Other(button),
+                state: ElementState::Pressed,
+                ..
+            } => match side_button(*button) {
+                Some(action) => {
+                    self.perform(action);
                     true
                 }
-                _ => false,
+                None => false,
             },
+
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => {
+                // Typing into a text field should not move the view
+                if self.ui_wrapper.ctx.wants_keyboard_input() {
+                    return true;
+                }
+                match self.keybindings.action(*key) {
+                    // Quitting is left to the event loop
+                    Some(Action::Quit) | None => false,
+                    Some(action) => {
+                        self.perform(action);
+                        true
+                    }
+                }
+            }
+
+            WindowEvent::DroppedFile(path) => {
+                self.open_scene(path);
+                true
+            }
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+                let [x, y] = self.shader_uniform.mouse.position();
+                self.camera.scroll(x as f64, y as f64, lines as f64);
+                true
+            }
             _ => false,
         }
     }
 
+    fn perform(&mut self, action: Action) {
+        let interface = &mut self.ui_wrapper.interface;
+        match action {
+            Action::PanUp => self.camera.pan(0.0, -PAN_STEP),
+            Action::PanDown => self.camera.pan(0.0, PAN_STEP),
+            Action::PanLeft => self.camera.pan(-PAN_STEP, 0.0),
+            Action::PanRight => self.camera.pan(PAN_STEP, 0.0),
+            Action::ZoomIn => self.camera.zoom(ZOOM_LINES),
+            Action::ZoomOut => self.camera.zoom(-ZOOM_LINES),
+            Action::IncreaseIterations => {
+                interface.auto_iterations.enabled = false;
+                interface.iterations = ((interface.iterations as f32 * ITERATION_STEP) as i32)
+                    .max(interface.iterations + 1);
+            }
+            Action::DecreaseIterations => {
+                interface.auto_iterations.enabled = false;
+                interface.iterations =
+                    ((interface.iterations as f32 / ITERATION_STEP) as i32).max(1);
+            }
+            Action::ResetView => self.camera.reset(),
+            Action::HistoryBack => {
+                if let Some(view) = self.history.back() {
+                    self.apply_view(&view);
+                }
+            }
+            Action::HistoryForward => {
+                if let Some(view) = self.history.forward() {
+                    self.apply_view(&view);
+                }
+            }
+            Action::Screenshot => self.screenshot(),
+            Action::ToggleUi => interface.visible = !interface.visible,
+            Action::ToggleOverlay => interface.show_overlay = !interface.show_overlay,
+            Action::FindNucleus => {
+                let [x, y] = self.shader_uniform.mouse.position();
+                self.find_nucleus(self.camera.get_coordinate(x as f64, y as f64));
+            }
+            Action::FindMisiurewicz => {
+                let [x, y] = self.shader_uniform.mouse.position();
+                self.find_misiurewicz(self.camera.get_coordinate(x as f64, y as f64));
+            }
+            Action::Quit => {}
+        }
+    }
+
+    fn execute(&mut self, command: ui::Command) {
+        match command {
+            ui::Command::GoTo(view) => self.apply_view(&view),
+            ui::Command::HistoryJump(index) => {
+                if let Some(view) = self.history.go_to(index) {
+                    self.apply_view(&view);
+                }
+            }
+            ui::Command::SaveBookmark(name) => {
+                let view = self.current_view();
+                self.bookmarks.add(Bookmark { name, view });
+            }
+            ui::Command::DeleteBookmark(index) => self.bookmarks.remove(index),
+            ui::Command::OpenScene(path) => self.open_scene(&path),
+            ui::Command::SaveScene(path) => self.save_scene(&path),
+            ui::Command::FindNucleus => self.find_nucleus(self.camera.centre),
+            ui::Command::FindMisiurewicz => self.find_misiurewicz(self.camera.centre),
+            ui::Command::TraceRay(angle) => self.trace_ray(angle),
+            ui::Command::ExportImage {
+                path,
+                format,
+                width,
+                height,
+            } => self.start_export(path, format, width, height),
+            ui::Command::CancelExport => self.cancel_export(),
+            ui::Command::SetZoomStart => {
+                self.ui_wrapper.interface.zoom_start = Some(self.current_frame());
+            }
+            ui::Command::SetZoomEnd => {
+                self.ui_wrapper.interface.zoom_end = Some(self.current_frame());
+            }
+            ui::Command::Record {
+                frames,
+                destination,
+                width,
+                height,
+            } => self.start_recording(destination, frames, [width, height]),
+            ui::Command::RecordPaletteCycle {
+                frame_rate,
+                destination,
+                width,
+                height,
+            } => {
+                let cycle = self.ui_wrapper.interface.palette_cycle;
+                let frames = cycle.frames(self.current_frame(), frame_rate);
+                self.start_recording(destination, frames, [width, height]);
+            }
+            ui::Command::AddKeyframe(time) => {
+                let frame = self.current_frame();
+                self.ui_wrapper
+                    .interface
+                    .timeline
+                    .insert(animation::Keyframe {
+                        time,
+                        frame,
+                        interpolation: animation::Interpolation::LogZoom,
+                    });
+            }
+            ui::Command::UpdateKeyframe(index) => {
+                let frame = self.current_frame();
+                if let Some(keyframe) = self.ui_wrapper.interface.timeline.keyframes.get_mut(index)
+                {
+                    keyframe.frame = frame;
+                }
+            }
+            ui::Command::SeekTimeline(time) => self.seek_timeline(time),
+            ui::Command::CopyLocation => {
+                let token = share::encode(&self.current_scene());
+                self.clipboard.set(token.clone());
+                self.report(format!("Copied {}", token), &[]);
+            }
+            ui::Command::PasteLocation => {
+                let text = self.clipboard.get().unwrap_or_default();
+                match share::decode(&text) {
+                    Ok(scene) => {
+                        self.apply_scene(&scene);
+                        self.report("Pasted location".to_string(), &[]);
+                    }
+                    Err(e) => self.report(format!("Could not paste location: {}", e), &[]),
+                }
+            }
+        }
+    }
+
+    fn current_scene(&self) -> Scene {
+        Scene::from_view(&self.current_view(), self.ui_wrapper.interface.colouring)
+    }
+
+    // Unlike apply_view this jumps straight to the scene, as it is usually somewhere new
+    fn apply_scene(&mut self, scene: &Scene) {
+        let view = scene.view();
+        self.camera.jump_to(view.centre, view.axis_range);
+        self.apply_view(&view);
+        self.ui_wrapper.interface.colouring = scene.colouring;
+    }
+
+    fn open_scene(&mut self, path: &Path) {
+        match Scene::import(path) {
+            Ok(imported) => {
+                self.apply_scene(&imported.scene);
+                self.report(format!("Opened {}", path.display()), &imported.warnings);
+            }
+            Err(e) => self.report(format!("Could not open {}: {}", path.display(), e), &[]),
+        }
+    }
+
+    fn save_scene(&mut self, path: &Path) {
+        match self.current_scene().export(path) {
+            Ok(warnings) => self.report(format!("Saved {}", path.display()), &warnings),
+            Err(e) => self.report(format!("Could not save {}: {}", path.display(), e), &[]),
+        }
+    }
+
+    // The analysis tools only understand z^2 + c, this reports when another formula is set
+    fn check_standard_formula(&mut self, tool: &str) -> bool {
+        let interface = &self.ui_wrapper.interface;
+        let formula = scene::Formula {
+            exponent: interface.exponent,
+            value: interface.value,
+            julia: interface.julia,
+            seed: interface.seed,
+        };
+        if formula.julia {
+            self.report(
+                format!("{} only works on the Mandelbrot set, not Julia sets", tool),
+                &[],
+            );
+            return false;
+        }
+        if !formula.is_standard() {
+            self.report(
+                format!("{} only works with exponent 2 and value 2", tool),
+                &[],
+            );
+        }
+        formula.is_standard()
+    }
+
+    fn trace_ray(&mut self, angle: rays::Angle) {
+        if !self.check_standard_formula("Ray tracing") {
+            return;
+        }
+        let ray = rays::Ray::trace(angle);
+        self.report(
+            format!("Traced the ray at {} = {}", angle, angle.binary()),
+            &[],
+        );
+        self.ui_wrapper.interface.rays.push(ray);
+    }
+
+    // Lists the external angles of rays passing near `c`, at the cursor when Alt is held
+    fn estimate_angles(&mut self, c: [f64; 2]) {
+        if !self.check_standard_formula("Angle estimation") {
+            return;
+        }
+        if self.angle_estimation.is_some() {
+            self.report("Still estimating the last angles".to_string(), &[]);
+            return;
+        }
+        let interface = &mut self.ui_wrapper.interface;
+        // Samples a few pixels away when c is inside the set
+        let radius = 4.0 * self.camera.axis_range / self.size.height.max(1) as f64;
+        let iterations = interface.iterations;
+        interface.angle_estimates.clear();
+        interface.estimating_angles = true;
+        self.angle_estimation = Some(std::thread::spawn(move || {
+            rays::angles_near(c, radius, iterations)
+        }));
+    }
+
+    // Shows the angles once the estimate started by estimate_angles has finished
+    fn advance_angle_estimation(&mut self) {
+        if !self
+            .angle_estimation
+            .as_ref()
+            .is_some_and(|job| job.is_finished())
+        {
+            return;
+        }
+        let interface = &mut self.ui_wrapper.interface;
+        interface.estimating_angles = false;
+        let status = match self.angle_estimation.take().map(JoinHandle::join) {
+            Some(Ok(estimates)) => {
+                interface.angle_estimates = estimates;
+                match interface.angle_estimates.len() {
+                    0 => "No escaping points nearby, try more iterations".to_string(),
+                    count => format!("Estimated {} external angles", count),
+                }
+            }
+            _ => "Angle estimation failed".to_string(),
+        };
+        self.report(status, &[]);
+    }
+
+    // Lists the Misiurewicz points with the preperiod and period chosen in the interface, or
+    // all up to them, found by Newton's method from starts spread over the view around `start`
+    fn find_misiurewicz(&mut self, start: [f64; 2]) {
+        if !self.check_standard_formula("The Misiurewicz finder") {
+            return;
+        }
+        let interface = &mut self.ui_wrapper.interface;
+
+        let (preperiod, period) = (
+            interface.misiurewicz_preperiod,
+            interface.misiurewicz_period,
+        );
+        let pairs: Vec<(usize, usize)> = if interface.misiurewicz_up_to {
+            (2..=preperiod)
+                .flat_map(|k| (1..=period).map(move |p| (k, p)))
+                .collect()
+        } else {
+            vec![(preperiod, period)]
+        };
+        interface.misiurewicz = misiurewicz::search(start, self.camera.axis_range / 2.0, &pairs);
+        let status = format!("Found {} Misiurewicz points", interface.misiurewicz.len());
+        self.report(status, &[]);
+    }
+
+    // Looks for the nucleus of the minibrot nearest to `start`, with the period chosen in the
+    // interface or detected from the orbit of `start`
+    fn find_nucleus(&mut self, start: [f64; 2]) {
+        if !self.check_standard_formula("The nucleus finder") {
+            return;
+        }
+        let interface = &mut self.ui_wrapper.interface;
+
+        let period = match interface.nucleus_period {
+            0 => nucleus::detect_period(start, interface.iterations),
+            period => Some(period),
+        };
+        let found = period
+            .ok_or(nucleus::NucleusError::NoPeriod)
+            .and_then(|period| nucleus::Nucleus::find(start, period));
+        match found {
+            Ok(found) => {
+                let status = format!("Found a period {} nucleus", found.period);
+                self.ui_wrapper.interface.nucleus = Some(found);
+                self.report(status, &[]);
+            }
+            Err(e) => self.report(format!("Could not find a nucleus: {}", e), &[]),
+        }
+    }
+
+    // Shows the outcome of a file operation in the interface and the log
+    fn report(&mut self, status: String, warnings: &[String]) {
+        log::info!("{}", status);
+        let mut lines = vec![status];
+        for warning in warnings {
+            log::warn!("{}", warning);
+            lines.push(format!("Warning: {}", warning));
+        }
+        self.ui_wrapper.interface.status = lines.join("\n");
+    }
+
+    fn current_view(&self) -> View {
+        let interface = &self.ui_wrapper.interface;
+        View {
+            centre: self.camera.centre,
+            axis_range: self.camera.axis_range,
+            iterations: interface.iterations,
+            value: interface.value,
+            exponent: interface.exponent,
+            julia: interface.julia,
+            seed: interface.seed,
+        }
+    }
+
+    fn current_frame(&self) -> animation::Frame {
+        animation::Frame {
+            view: self.current_view(),
+            colouring: self.ui_wrapper.interface.colouring,
+        }
+    }
+
+    // Jumps to the timeline's frame at `time`
+    fn seek_timeline(&mut self, time: f64) {
+        let Some(frame) = self.ui_wrapper.interface.timeline.frame(time) else {
+            return;
+        };
+        self.camera
+            .jump_to(frame.view.centre, frame.view.axis_range);
+        self.apply_view(&frame.view);
+        self.ui_wrapper.interface.colouring = frame.colouring;
+    }
+
+    // Moves the timeline's playback on by `dt` seconds
+    fn play_timeline(&mut self, dt: f64) {
+        let interface = &mut self.ui_wrapper.interface;
+        if !interface.timeline_playing {
+            return;
+        }
+        let duration = interface.timeline.duration();
+        interface.timeline_time += dt;
+        if interface.timeline_time >= duration {
+            if interface.timeline_looping && duration > 0.0 {
+                interface.timeline_time %= duration;
+            } else {
+                interface.timeline_time = duration;
+                interface.timeline_playing = false;
+            }
+        }
+        self.seek_timeline(self.ui_wrapper.interface.timeline_time);
+    }
+
+    // Eases the camera to `view` and loads its parameters into the interface
+    fn apply_view(&mut self, view: &View) {
+        self.camera.set_target(view.centre, view.axis_range);
+        let interface = &mut self.ui_wrapper.interface;
+        interface.iterations = view.iterations;
+        interface.value = view.value;
+        interface.exponent = view.exponent;
+        interface.julia = view.julia;
+        interface.seed = view.seed;
+    }
+
+    fn render_thumbnail(&self, view: &View) -> egui::TextureHandle {
+        let [width, height] = THUMBNAIL_SIZE;
+        let viewport = Viewport::from_view(view, width, height);
+        let params = FractalParams::from_view(view, self.ui_wrapper.interface.colouring);
+        let pixels = self.renderer.render_to_image(&viewport, &params);
+        self.ui_wrapper.ctx.load_texture(
+            "thumbnail",
+            egui::ColorImage::from_rgba_unmultiplied([width as usize, height as usize], &pixels),
+            egui::TextureOptions::LINEAR,
+        )
+    }
+
+    // Renders the fractal described by `uniform` offscreen at its resolution, as RGBA8 rows
+    fn render_image(&self, uniform: &ShaderUniform) -> Vec<u8> {
+        let size = [uniform.resolution[0] as u32, uniform.resolution[1] as u32];
+        self.renderer
+            .render_tile(uniform, [0, 0], size, export::ImageFormat::Png8)
+    }
+
+    fn start_export(
+        &mut self,
+        path: PathBuf,
+        format: export::ImageFormat,
+        width: u32,
+        height: u32,
+    ) {
+        if self.is_exporting() {
+            return self.report("An export is already running".to_string(), &[]);
+        }
+        let scene = match self.current_scene().to_toml() {
+            Ok(scene) => scene,
+            Err(e) => return self.report(format!("Could not export: {}", e), &[]),
+        };
+        let max_tile_width = self.renderer.max_tile_width();
+        match export::TiledExport::new(&path, format, width, height, max_tile_width, &scene) {
+            Ok(tiled_export) => {
+                let mut uniform = self.shader_uniform;
+                uniform.update_view(&self.current_view());
+                uniform.update_resolution(width as f32, height as f32);
+                self.export_uniform = uniform;
+                self.tiled_export = Some(tiled_export);
+                self.report(format!("Exporting {}", path.display()), &[]);
+            }
+            Err(e) => self.report(format!("Could not export {}: {}", path.display(), e), &[]),
+        }
+    }
+
+    fn is_exporting(&self) -> bool {
+        self.tiled_export.is_some() || self.recording.is_some()
+    }
+
+    fn start_recording(
+        &mut self,
+        destination: animation::Destination,
+        frames: Vec<animation::Frame>,
+        size: [u32; 2],
+    ) {
+        if self.is_exporting() {
+            return self.report("An export is already running".to_string(), &[]);
+        }
+        let mut warnings = Vec::new();
+        let destination = match destination {
+            animation::Destination::Video { path, .. } if !video::is_available() => {
+                let directory = path.with_extension("");
+                warnings.push(format!(
+                    "ffmpeg was not found, writing PNG frames to {} instead",
+                    directory.display()
+                ));
+                animation::Destination::Images(directory)
+            }
+            destination => destination,
+        };
+        let path = destination.path().to_path_buf();
+        match animation::Recording::new(destination, frames, size) {
+            Ok(recording) => {
+                let status = format!(
+                    "Recording {} frames to {}",
+                    recording.frame_count(),
+                    path.display()
+                );
+                self.recording = Some(recording);
+                self.report(status, &warnings);
+            }
+            Err(e) => self.report(
+                format!("Could not record to {}: {}", path.display(), e),
+                &warnings,
+            ),
+        }
+    }
+
+    // Starts the tiled export of the next frame of the running recording
+    fn start_frame(&mut self) {
+        let Some(recording) = &self.recording else {
+            return;
+        };
+        let Some((path, frame)) = recording.next_frame() else {
+            return;
+        };
+        let [width, height] = recording.size();
+        let max_tile_width = self.renderer.max_tile_width();
+        let result = if recording.is_video() {
+            Ok(export::TiledExport::raw(
+                &path,
+                width,
+                height,
+                max_tile_width,
+            ))
+        } else {
+            let scene = Scene::from_view(&frame.view, frame.colouring).to_toml();
+            scene.map_err(|e| e.to_string()).and_then(|scene| {
+                let format = export::ImageFormat::Png8;
+                export::TiledExport::new(&path, format, width, height, max_tile_width, &scene)
+                    .map_err(|e| e.to_string())
+            })
+        };
+        match result {
+            Ok(tiled_export) => {
+                let mut uniform = self.shader_uniform;
+                uniform.update_view(&frame.view);
+                uniform.update_colouring(&frame.colouring);
+                uniform.update_resolution(width as f32, height as f32);
+                self.export_uniform = uniform;
+                self.tiled_export = Some(tiled_export);
+            }
+            Err(e) => {
+                self.recording = None;
+                self.report(format!("Could not record {}: {}", path.display(), e), &[]);
+            }
+        }
+    }
+
+    fn cancel_export(&mut self) {
+        let tiled_export = self.tiled_export.take();
+        if let Some(tiled_export) = &tiled_export {
+            let _ = std::fs::remove_file(tiled_export.path());
+        }
+        if let Some(recording) = self.recording.take() {
+            let status = format!(
+                "Cancelled the recording after {} of {} frames",
+                recording.frames_done(),
+                recording.frame_count()
+            );
+            // Dropping the recording stops ffmpeg, its unfinished video is no use
+            if recording.is_video() {
+                let path = recording.path().to_path_buf();
+                drop(recording);
+                let _ = std::fs::remove_file(path);
+            }
+            self.report(status, &[]);
+        } else if let Some(tiled_export) = tiled_export {
+            self.report(
+                format!("Cancelled the export of {}", tiled_export.path().display()),
+                &[],
+            );
+        }
+        self.show_export_progress(0.0);
+    }
+
+    fn show_export_progress(&mut self, tile_progress: f32) {
+        let interface = &mut self.ui_wrapper.interface;
+        interface.export_progress = None;
+        interface.recording_progress = None;
+        if let Some(recording) = &self.recording {
+            interface.recording_progress = Some((
+                recording.frames_done(),
+                recording.frame_count(),
+                tile_progress,
+            ));
+        } else if self.tiled_export.is_some() {
+            interface.export_progress = Some(tile_progress);
+        }
+    }
+
+    // Renders tiles of the running export until this frame's share of time is used up
+    fn advance_export(&mut self) {
+        if self.tiled_export.is_none() {
+            self.start_frame();
+        }
+        let Some(mut tiled_export) = self.tiled_export.take() else {
+            return;
+        };
+        let start = Instant::now();
+        let mut result = Ok(());
+        while let Some((offset, size)) = tiled_export.next_tile() {
+            let pixels = self.renderer.render_tile(
+                &self.export_uniform,
+                offset,
+                size,
+                tiled_export.format(),
+            );
+            result = tiled_export.add_tile(&pixels);
+            if result.is_err() || start.elapsed() >= EXPORT_TIME_PER_FRAME {
+                break;
+            }
+        }
+
+        if result.is_ok() && !tiled_export.is_finished() {
+            let progress = tiled_export.progress();
+            self.tiled_export = Some(tiled_export);
+            return self.show_export_progress(progress);
+        }
+        let path = tiled_export.path().to_path_buf();
+        let result = result.and_then(|_| match self.recording.as_mut() {
+            Some(recording) if recording.is_video() => {
+                recording.write_frame(&tiled_export.into_pixels())
+            }
+            _ => tiled_export.finish(),
+        });
+        match (result, self.recording.take()) {
+            (Ok(()), Some(mut recording)) => {
+                recording.frame_finished();
+                if !recording.is_finished() {
+                    self.recording = Some(recording);
+                } else {
+                    let status = format!(
+                        "Recorded {} frames to {}",
+                        recording.frame_count(),
+                        recording.path().display()
+                    );
+                    match recording.finish() {
+                        Ok(()) => self.report(status, &[]),
+                        Err(e) => {
+                            let _ = std::fs::remove_file(&path);
+                            self.report(format!("Could not record {}: {}", path.display(), e), &[]);
+                        }
+                    }
+                }
+            }
+            (Err(e), Some(recording)) => {
+                drop(recording);
+                let _ = std::fs::remove_file(&path);
+                self.report(format!("Could not record {}: {}", path.display(), e), &[]);
+            }
+            (Ok(()), None) => self.report(format!("Exported {}", path.display()), &[]),
+            (Err(e), None) => {
+                let _ = std::fs::remove_file(&path);
+                self.report(format!("Could not export {}: {}", path.display(), e), &[])
+            }
+        }
+        self.show_export_progress(0.0);
+    }
+
+    fn screenshot(&self) {
+        let pixels = self.render_image(&self.shader_uniform);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let path = PathBuf::from(format!("mandelbrot-{}.png", timestamp));
+
+        let result = self.current_scene().to_toml().map_err(|e| e.to_string());
+        let result = result.and_then(|scene| {
+            export::write_png(&path, self.size.width, self.size.height, &pixels, &scene)
+                .map_err(|e| e.to_string())
+        });
+        match result {
+            Ok(()) => log::info!("saved screenshot to {}", path.display()),
+            Err(e) => log::error!("could not save screenshot to {}: {}", path.display(), e),
+        }
+    }
+
+    fn finish_selection(&mut self) {
+        if let Some([start, end]) = self.selection.take() {
+            let width = (start[0] - end[0]).abs();
+            let height = (start[1] - end[1]).abs();
+            if width >= MIN_SELECTION && height >= MIN_SELECTION {
+                self.camera.zoom_to_rect(
+                    [start[0] as f64, start[1] as f64],
+                    [end[0] as f64, end[1] as f64],
+                );
+            }
+        }
+    }
+
     fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_update).as_secs_f64();
+        self.last_update = now;
+        self.frame_time += (dt - self.frame_time) * FRAME_TIME_SMOOTHING;
+        if let Some(time) = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.poll(self.renderer.device()))
+        {
+            let smoothed = self.gpu_time.unwrap_or(time);
+            self.gpu_time = Some(smoothed + (time - smoothed) * FRAME_TIME_SMOOTHING);
+        }
+        self.play_timeline(dt);
+        self.camera.update(dt);
+        self.camera.write_uniform(
+            &mut self.shader_uniform.mouse,
+            &mut self.shader_uniform.axis_range,
+        );
+
+        for command in std::mem::take(&mut self.ui_wrapper.interface.commands) {
+            self.execute(command);
+        }
+        self.advance_export();
+        self.advance_angle_estimation();
+
+        let interface = &mut self.ui_wrapper.interface;
+        if interface.palette_cycle.enabled {
+            interface.colouring.offset = interface
+                .palette_cycle
+                .advance(interface.colouring.offset, dt as f32);
+        }
+
+        let view = self.current_view();
+        if self.history.observe(view, self.camera.is_settled()) {
+            let thumbnail = self.render_thumbnail(&view);
+            self.history.push(view, thumbnail);
+        }
+
+        // One bookmark thumbnail per frame keeps startup responsive
+        let missing = self.bookmarks.missing_thumbnail();
+        if let Some(view) = missing.map(|entry| entry.bookmark.view) {
+            let thumbnail = self.render_thumbnail(&view);
+            if let Some(entry) = self.bookmarks.missing_thumbnail() {
+                entry.thumbnail = Some(thumbnail);
+            }
+        }
+
+        let interface = &mut self.ui_wrapper.interface;
+        if let Some(count) = self.unresolved.poll(self.renderer.device()) {
+            let pixels = (self.size.width * self.size.height).max(1) as f32;
+            interface
+                .auto_iterations
+                .observe(count as f32 / pixels, self.camera.is_settled());
+        }
+        let auto_iterations = &interface.auto_iterations;
+        if auto_iterations.enabled {
+            interface.iterations = auto_iterations.iterations(self.camera.axis_range);
+        }
+        self.shader_uniform.count_unresolved =
+            (auto_iterations.enabled && auto_iterations.adaptive) as i32;
+
         if self.shader_uniform.iterations != self.ui_wrapper.interface.iterations {
             self.shader_uniform
                 .update_iterations(self.ui_wrapper.interface.iterations);
@@ -436,7 +1108,16 @@ impl State {
             self.shader_uniform
                 .update_exponent(self.ui_wrapper.interface.exponent);
         }
-        self.queue.write_buffer(
+
+        self.shader_uniform.update_julia(
+            self.ui_wrapper.interface.julia,
+            self.ui_wrapper.interface.seed,
+        );
+
+        self.shader_uniform
+            .update_colouring(&self.ui_wrapper.interface.colouring);
+
+        self.renderer.queue().write_buffer(
             &self.shader_buffer,
             0,
             bytemuck::cast_slice(&[self.shader_uniform]),
@@ -448,13 +1129,57 @@ impl State {
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+        let mut encoder =
+            self.renderer
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                });
         let mut command_buffer = Vec::new();
 
+        let timer = self.gpu_timer.as_mut().filter(|timer| timer.ready());
+        if let Some(timer) = &timer {
+            timer.begin(&mut encoder);
+        }
+
+        let data_key = self.shader_uniform.data_key();
+        let iterate = self
+            .last_data
+            .is_none_or(|last| bytemuck::bytes_of(&last) != bytemuck::bytes_of(&data_key));
+        if iterate {
+            let wants_count = self.shader_uniform.count_unresolved != 0;
+            let counting = wants_count && self.unresolved.ready();
+            if counting {
+                self.unresolved.begin(&mut encoder);
+            }
+            {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Data Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &self.data_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+
+                render_pass.set_pipeline(self.renderer.data_pipeline());
+                render_pass.set_bind_group(0, &self.shader_bind_group, &[]);
+                self.renderer.draw_quad(&mut render_pass);
+            }
+            if counting {
+                self.unresolved.end(&mut encoder);
+            }
+            // Without a count the adaptive limit would never hear about this view, so it is
+            // iterated again until the counter is free
+            if counting || !wants_count {
+                self.last_data = Some(data_key);
+            }
+        }
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -471,21 +1196,91 @@ impl State {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.shader_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+            render_pass.set_bind_group(1, &self.data_bind_group, &[]);
+            self.renderer.draw_quad(&mut render_pass);
+        }
+
+        if let Some(timer) = timer {
+            timer.end(&mut encoder);
         }
 
         // egui pass
         {
             let screen_descriptor = egui_wgpu::renderer::ScreenDescriptor {
-                size_in_pixels: [self.size.width as u32, self.size.height as u32],
+                size_in_pixels: [self.size.width, self.size.height],
                 pixels_per_point: self.ui_wrapper.winit_ctx.pixels_per_point(),
             };
 
+            self.ui_wrapper.interface.selection = self.selection.map(|[start, end]| {
+                let pixels_per_point = screen_descriptor.pixels_per_point;
+                egui::Rect::from_two_pos(
+                    egui::pos2(start[0] / pixels_per_point, start[1] / pixels_per_point),
+                    egui::pos2(end[0] / pixels_per_point, end[1] / pixels_per_point),
+                )
+            });
+
+            let [x, y] = self.shader_uniform.mouse.position();
+            // Holding Ctrl over the fractal traces the orbit of the point under the cursor
+            let interface = &mut self.ui_wrapper.interface;
+            interface.orbit_points.clear();
+            if self.modifiers.ctrl() && !self.ui_wrapper.ctx.is_pointer_over_area() {
+                let point = self.camera.get_coordinate(x as f64, y as f64);
+                let (start, c) = if interface.julia {
+                    (point, interface.seed)
+                } else {
+                    ([0.0, 0.0], point)
+                };
+                let (exponent, value) = (self.shader_uniform.exponent, self.shader_uniform.value);
+                let iterations = self.shader_uniform.iterations;
+                // Kept from the last frame while the cursor and formula stay put
+                let orbit = match interface.orbit.take() {
+                    Some(orbit) if orbit.is_of(start, c, exponent, value, iterations) => orbit,
+                    _ => orbit::Orbit::compute(start, c, exponent, value, iterations),
+                };
+                let pixels_per_point = screen_descriptor.pixels_per_point as f64;
+                interface.orbit_points.extend(orbit.points.iter().map(|&z| {
+                    let [px, py] = self.camera.get_screen_position(z);
+                    egui::pos2(
+                        (px / pixels_per_point) as f32,
+                        (py / pixels_per_point) as f32,
+                    )
+                }));
+                interface.orbit = Some(orbit);
+            }
+            let pixels_per_point = screen_descriptor.pixels_per_point as f64;
+            interface.ray_lines = interface
+                .rays
+                .iter()
+                .map(|ray| {
+                    ray.points
+                        .iter()
+                        .map(|&c| {
+                            let [px, py] = self.camera.get_screen_position(c);
+                            egui::pos2(
+                                (px / pixels_per_point) as f32,
+                                (py / pixels_per_point) as f32,
+                            )
+                        })
+                        .collect()
+                })
+                .collect();
+
+            self.ui_wrapper.interface.info = ui::Info {
+                cursor: self.camera.get_coordinate(x as f64, y as f64),
+                centre: self.camera.centre,
+                axis_range: self.camera.axis_range,
+                zoom: camera::Camera::DEFAULT_RANGE / self.camera.axis_range,
+                iterations: self.shader_uniform.iterations,
+                frame_time: self.frame_time,
+                gpu_time: self.gpu_time,
+                adapter: self.adapter_name.clone(),
+            };
+
             let input = self.ui_wrapper.winit_ctx.take_egui_input(&self.window);
             let output = self.ui_wrapper.ctx.run(input, |ctx| {
-                self.ui_wrapper.interface.ui(ctx);
+                self.ui_wrapper
+                    .interface
+                    .ui(ctx, &self.history, &self.bookmarks);
             });
 
             self.ui_wrapper.winit_ctx.handle_platform_output(
@@ -499,8 +1294,8 @@ impl State {
 
             for (id, image_delta) in &texture_deltas.set {
                 self.ui_wrapper.wgpu_ctx.update_texture(
-                    &self.device,
-                    &self.queue,
+                    self.renderer.device(),
+                    self.renderer.queue(),
                     *id,
                     image_delta,
                 );
@@ -511,8 +1306,8 @@ impl State {
             }
 
             let ui_commands = self.ui_wrapper.wgpu_ctx.update_buffers(
-                &self.device,
-                &self.queue,
+                self.renderer.device(),
+                self.renderer.queue(),
                 &mut encoder,
                 &paint_jobs,
                 &screen_descriptor,
@@ -535,61 +1330,104 @@ impl State {
                 .wgpu_ctx
                 .render(&mut render_pass, &paint_jobs, &screen_descriptor);
 
-            command_buffer.extend(ui_commands.into_iter());
+            command_buffer.extend(ui_commands);
         }
 
-        self.queue.submit(
+        self.renderer.queue().submit(
             command_buffer
                 .into_iter()
                 .chain(std::iter::once(encoder.finish())),
         );
 
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.request();
+        }
+        self.unresolved.request();
+
         output.present();
 
         Ok(())
     }
 }
 
-pub async fn run() {
+// Mouse side buttons arrive as Other(1)/Other(2) on Windows, Other(3)/Other(4) on macOS,
+// Other(8)/Other(9) on X11 and as the BTN_SIDE/BTN_EXTRA codes on Wayland
+fn side_button(button: u16) -> Option<Action> {
+    match button {
+        1 | 3 | 8 | 0x113 => Some(Action::HistoryBack),
+        2 | 4 | 9 | 0x114 => Some(Action::HistoryForward),
+        _ => None,
+    }
+}
+
+// Command line options, e.g. `mandelbrot-explorer --scene deep-zoom.toml`. A bare path, such
+// as an exported PNG, is opened as the scene too.
+#[derive(Default)]
+struct Options {
+    scene: Option<PathBuf>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--scene" => options.scene = args.next().map(PathBuf::from),
+                _ => match arg.strip_prefix("--scene=") {
+                    Some(path) => options.scene = Some(PathBuf::from(path)),
+                    None if !arg.starts_with('-') => options.scene = Some(PathBuf::from(arg)),
+                    None => log::warn!("ignoring unknown argument {}", arg),
+                },
+            }
+        }
+        options
+    }
+}
+
+pub async fn run() -> Result<(), SetupError> {
     // Setup logging
     env_logger::init();
 
+    let options = Options::from_args();
+
     // Window Setup
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new().build(&event_loop)?;
     window.set_title("mandelbrot");
 
     // State
-    let mut state = State::new(window).await;
+    let mut state = State::new(window).await?;
+    if let Some(path) = &options.scene {
+        state.open_scene(path);
+    }
 
     // Event Loop
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == state.window().id() => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
+        } if window_id == state.window().id() && !state.input(event) => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(key),
                         ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => {
-                        state.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        state.resize(**new_inner_size);
-                    }
-                    _ => {}
-                }
+                    },
+                ..
+            } if state.keybindings.action(*key) == Some(Action::Quit) => {
+                *control_flow = ControlFlow::Exit
             }
-        }
+            WindowEvent::Resized(physical_size) => {
+                state.resize(*physical_size);
+            }
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                state.resize(**new_inner_size);
+            }
+            _ => {}
+        },
         Event::RedrawRequested(window_id) if window_id == state.window().id() => {
             state.update();
             match state.render() {
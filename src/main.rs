@@ -1,7 +1,8 @@
-mod ui;
-
 use mandelbrot_explorer::run;
 
 fn main() {
-    pollster::block_on(run());
+    if let Err(e) = pollster::block_on(run()) {
+        eprintln!("mandelbrot-explorer: {}", e);
+        std::process::exit(1);
+    }
 }
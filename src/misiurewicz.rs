@@ -0,0 +1,152 @@
+// Finds Misiurewicz points of z^2 + c, where the orbit of 0 lands on a repelling cycle of length
+// `period` after `preperiod` iterations, i.e. z_(preperiod + period) = z_preperiod
+
+use crate::complex::{add, div, length, mul, scale, sub};
+
+const MAX_NEWTON_STEPS: usize = 64;
+const STEP_TOLERANCE: f64 = 1e-15;
+// Newton is started from a GRID x GRID square of points around the search position
+const GRID: usize = 5;
+const MAX_CANDIDATES: usize = 32;
+// Relative distance under which two orbit points count as equal when labelling a candidate
+const LABEL_TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Copy)]
+pub struct MisiurewiczPoint {
+    pub c: [f64; 2],
+    pub preperiod: usize,
+    pub period: usize,
+}
+
+// One Newton step towards a root of
+//
+//     (z_(k + p) - z_k) / product over i < k of (z_(i + p) - z_i)
+//
+// where the divisors remove the points of lower preperiod, including the period p nuclei, that
+// would otherwise attract most starting points. The step is 1 / (logarithmic derivative).
+fn newton_step(c: [f64; 2], preperiod: usize, period: usize) -> Option<[f64; 2]> {
+    let mut orbit = Vec::with_capacity(preperiod + period + 1);
+    let mut z = [0.0, 0.0];
+    let mut dz = [0.0, 0.0];
+    orbit.push((z, dz));
+    for _ in 0..preperiod + period {
+        dz = add(mul(scale(z, 2.0), dz), [1.0, 0.0]);
+        z = add(mul(z, z), c);
+        orbit.push((z, dz));
+    }
+
+    let difference = |i: usize| {
+        let (a, da) = orbit[i + period];
+        let (b, db) = orbit[i];
+        (sub(a, b), sub(da, db))
+    };
+    let (f, df) = difference(preperiod);
+    let mut log_derivative = div(df, f);
+    for i in 0..preperiod {
+        let (g, dg) = difference(i);
+        log_derivative = sub(log_derivative, div(dg, g));
+    }
+    let step = div([1.0, 0.0], log_derivative);
+    (step[0].is_finite() && step[1].is_finite()).then_some(step)
+}
+
+fn newton(start: [f64; 2], preperiod: usize, period: usize) -> Option<[f64; 2]> {
+    let mut c = start;
+    for _ in 0..MAX_NEWTON_STEPS {
+        let step = newton_step(c, preperiod, period)?;
+        c = sub(c, step);
+        if length(step) <= STEP_TOLERANCE * length(c).max(1.0) {
+            return Some(c);
+        }
+    }
+    None
+}
+
+// Smallest preperiod and period the orbit of c actually has, within the given limits
+fn label(c: [f64; 2], preperiod: usize, period: usize) -> Option<(usize, usize)> {
+    let mut orbit = vec![[0.0, 0.0]];
+    for _ in 0..preperiod + period {
+        let z = *orbit.last().unwrap();
+        orbit.push(add(mul(z, z), c));
+    }
+    let tolerance = LABEL_TOLERANCE * length(c).max(1.0);
+    (1..=period)
+        .filter(|p| period.is_multiple_of(*p))
+        .find_map(|p| {
+            (1..=preperiod)
+                .find(|&k| length(sub(orbit[k + p], orbit[k])) < tolerance)
+                .map(|k| (k, p))
+        })
+        // A preperiod of 1 or less means c is periodic, which makes it a nucleus
+        .filter(|&(k, _)| k >= 2)
+}
+
+// Runs Newton for each (preperiod, period) pair from a grid of starts spanning `radius` around
+// `start`, and returns the distinct points found, nearest first
+pub fn search(start: [f64; 2], radius: f64, pairs: &[(usize, usize)]) -> Vec<MisiurewiczPoint> {
+    let mut found: Vec<MisiurewiczPoint> = Vec::new();
+    for &(preperiod, period) in pairs {
+        for row in 0..GRID {
+            for column in 0..GRID {
+                let offset = |index: usize| (index as f64 / (GRID - 1) as f64 * 2.0 - 1.0) * radius;
+                let seed = add(start, [offset(column), offset(row)]);
+                let Some(c) = newton(seed, preperiod, period) else {
+                    continue;
+                };
+                let Some((preperiod, period)) = label(c, preperiod, period) else {
+                    continue;
+                };
+                let duplicate = found.iter().any(|point| {
+                    length(sub(point.c, c)) < LABEL_TOLERANCE * radius.max(f64::EPSILON)
+                });
+                if !duplicate {
+                    found.push(MisiurewiczPoint {
+                        c,
+                        preperiod,
+                        period,
+                    });
+                }
+            }
+        }
+    }
+
+    found.sort_by(|a, b| length(sub(a.c, start)).total_cmp(&length(sub(b.c, start))));
+    found.truncate(MAX_CANDIDATES);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_found(start: [f64; 2], pair: (usize, usize), expected: [f64; 2]) {
+        let points = search(start, 0.05, &[pair]);
+        let nearest = points.first().expect("no point found");
+        assert!(
+            length(sub(nearest.c, expected)) < 1e-12,
+            "found {:?}, expected {expected:?}",
+            nearest.c
+        );
+        assert_eq!((nearest.preperiod, nearest.period), pair);
+    }
+
+    #[test]
+    fn finds_i() {
+        // 0, i, -1 + i, -i, -1 + i, ...
+        assert_found([0.02, 0.97], (2, 2), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn finds_the_tip() {
+        // 0, -2, 2, 2, ...
+        assert_found([-1.98, 0.01], (2, 1), [-2.0, 0.0]);
+    }
+
+    #[test]
+    fn labels_with_the_smallest_preperiod_and_period() {
+        assert_eq!(label([0.0, 1.0], 4, 4), Some((2, 2)));
+        assert_eq!(label([-2.0, 0.0], 3, 2), Some((2, 1)));
+        // -1 is the period 2 nucleus, not a Misiurewicz point
+        assert_eq!(label([-1.0, 0.0], 2, 2), None);
+    }
+}
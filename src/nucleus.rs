@@ -0,0 +1,244 @@
+// Finds the nucleus of a hyperbolic component of z^2 + c, the point whose orbit returns to
+// zero after exactly `period` iterations, with Newton's method in double-double precision
+
+use crate::{
+    camera::Camera,
+    complex::{add, div, length, mul},
+    double_double::{self as dd, DoubleDouble},
+};
+
+const MAX_NEWTON_STEPS: usize = 64;
+// Newton stops once a step moves c by less than this, relative to |c|
+const STEP_TOLERANCE: f64 = 1e-30;
+// Rounding in long orbits can keep the steps from getting that small, so Newton also stops
+// once they stop shrinking below this
+const NOISE_TOLERANCE: f64 = 1e-24;
+// The whole set, of size 1, fits vertically in this axis_range at any orientation
+const MINIBROT_RANGE: f64 = 2.5;
+
+pub struct Nucleus {
+    // To about 32 significant digits, beyond what the f64 view can show
+    pub centre: [DoubleDouble; 2],
+    pub period: usize,
+    // Scale of the minibrot relative to the whole set
+    pub size: f64,
+    // Angle of its cusp-to-tail axis relative to the whole set, in degrees
+    pub orientation: f64,
+    // Offset, relative to the nucleus and scaled by size, of where to centre the view
+    view_offset: [f64; 2],
+}
+
+#[derive(Debug)]
+pub enum NucleusError {
+    NoPeriod,
+    NotConverged,
+    // The minibrot is smaller than a double-double can resolve around its position
+    TooSmall,
+}
+
+impl std::fmt::Display for NucleusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NucleusError::NoPeriod => write!(f, "no period found, try more iterations"),
+            NucleusError::NotConverged => write!(f, "Newton's method did not converge"),
+            NucleusError::TooSmall => write!(f, "the minibrot is below double-double precision"),
+        }
+    }
+}
+
+// Period of the atom domain containing c: the iteration at which |z_n| is smallest so far
+// before escaping, which is the period of the most prominent minibrot nearby
+pub fn detect_period(c: [f64; 2], iterations: i32) -> Option<usize> {
+    let mut z = [0.0, 0.0];
+    let mut smallest = f64::INFINITY;
+    let mut period = None;
+    for n in 1..=iterations.max(1) as usize {
+        z = add(mul(z, z), c);
+        let magnitude = length(z);
+        if magnitude >= 4.0 {
+            break;
+        }
+        if magnitude < smallest {
+            smallest = magnitude;
+            period = Some(n);
+        }
+    }
+    period
+}
+
+impl Nucleus {
+    pub fn find(start: [f64; 2], period: usize) -> Result<Self, NucleusError> {
+        if period == 0 {
+            return Err(NucleusError::NoPeriod);
+        }
+
+        let mut c = dd::from_f64(start);
+        let mut converged = false;
+        let mut last_step = f64::INFINITY;
+        for _ in 0..MAX_NEWTON_STEPS {
+            // z_period(c) and its derivative with respect to c
+            let mut z = [DoubleDouble::ZERO; 2];
+            let mut dz = [DoubleDouble::ZERO; 2];
+            for _ in 0..period {
+                dz = dd::add(
+                    dd::mul(dd::scale(z, 2.0), dz),
+                    [DoubleDouble::ONE, DoubleDouble::ZERO],
+                );
+                z = dd::add(dd::mul(z, z), c);
+            }
+            let step = dd::div(z, dz);
+            if !step.iter().all(|part| part.is_finite()) {
+                return Err(NucleusError::NotConverged);
+            }
+            c = dd::sub(c, step);
+            let step = length(dd::to_f64(step)) / length(dd::to_f64(c)).max(1.0);
+            if step <= STEP_TOLERANCE || (step >= last_step && last_step <= NOISE_TOLERANCE) {
+                converged = true;
+                break;
+            }
+            last_step = step;
+        }
+        if !converged {
+            return Err(NucleusError::NotConverged);
+        }
+
+        let period = Self::exact_period(c, period);
+        let size = Self::size(c, period);
+        let scale = length(size);
+        if scale < DoubleDouble::EPSILON * length(dd::to_f64(c)).max(1.0) * 16.0 {
+            return Err(NucleusError::TooSmall);
+        }
+
+        Ok(Self {
+            centre: c,
+            period,
+            size: scale,
+            orientation: size[1].atan2(size[0]).to_degrees(),
+            view_offset: mul([Camera::DEFAULT_CENTRE[0], Camera::DEFAULT_CENTRE[1]], size),
+        })
+    }
+
+    // Newton may land on a nucleus whose period divides the one asked for
+    fn exact_period(c: [DoubleDouble; 2], period: usize) -> usize {
+        let tolerance = 1e-9 * length(dd::to_f64(c)).max(1.0);
+        let mut z = [DoubleDouble::ZERO; 2];
+        for n in 1..period {
+            z = dd::add(dd::mul(z, z), c);
+            if length(dd::to_f64(z)) < tolerance && period.is_multiple_of(n) {
+                return n;
+            }
+        }
+        period
+    }
+
+    // Complex size estimate of the minibrot: its magnitude is the scale relative to the whole
+    // set and its argument the rotation. Only the orbit needs the extra precision.
+    fn size(c: [DoubleDouble; 2], period: usize) -> [f64; 2] {
+        let mut z = [DoubleDouble::ZERO; 2];
+        let mut l = [1.0, 0.0];
+        let mut b = [1.0, 0.0];
+        for _ in 1..period {
+            z = dd::add(dd::mul(z, z), c);
+            l = mul(dd::to_f64(dd::scale(z, 2.0)), l);
+            b = add(b, div([1.0, 0.0], l));
+        }
+        div([1.0, 0.0], mul(b, mul(l, l)))
+    }
+
+    // Whether the f64 camera can frame the minibrot, which needs it to span several f64 steps
+    pub fn can_view(&self) -> bool {
+        self.size >= f64::EPSILON * length(dd::to_f64(self.centre)).max(1.0) * 16.0
+    }
+
+    // View centre and axis_range that frame the whole minibrot
+    pub fn view(&self) -> ([f64; 2], f64) {
+        (
+            add(dd::to_f64(self.centre), self.view_offset),
+            MINIBROT_RANGE * self.size,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: DoubleDouble, expected: &str) {
+        let expected: DoubleDouble = expected.parse().unwrap();
+        let error = (actual - expected).abs().to_f64();
+        assert!(error < 1e-30, "{actual} is {error:e} from {expected}");
+    }
+
+    #[test]
+    fn finds_the_period_3_real_nucleus() {
+        let nucleus = Nucleus::find([-1.75, 0.0], 3).unwrap();
+        assert_eq!(nucleus.period, 3);
+        assert_near(
+            nucleus.centre[0],
+            "-1.754877666246692760049508896358528691895",
+        );
+        assert_eq!(nucleus.centre[1], DoubleDouble::ZERO);
+        assert!(nucleus.orientation.abs() < 1e-6);
+        assert!(nucleus.can_view());
+    }
+
+    #[test]
+    fn finds_the_rabbit_nucleus() {
+        let nucleus = Nucleus::find([-0.12, 0.74], 3).unwrap();
+        assert_eq!(nucleus.period, 3);
+        assert_near(
+            nucleus.centre[0],
+            "-0.1225611668766536199752455518207356540527",
+        );
+        assert_near(
+            nucleus.centre[1],
+            "0.7448617666197442365931704286043923672402",
+        );
+    }
+
+    #[test]
+    fn reports_the_exact_period() {
+        // -1 is the period 2 nucleus, so also a root of z_4
+        let nucleus = Nucleus::find([-1.0001, 0.0], 4).unwrap();
+        assert_eq!(nucleus.period, 2);
+        assert_near(nucleus.centre[0], "-1");
+        let nucleus = Nucleus::find([-1.31, 0.0], 4).unwrap();
+        assert_eq!(nucleus.period, 4);
+        assert_near(
+            nucleus.centre[0],
+            "-1.310702641336832883563570797412180778502",
+        );
+    }
+
+    #[test]
+    fn finds_minibrots_below_f64_precision() {
+        // f64 can place its centre but not resolve its size
+        let nucleus = Nucleus::find([-2.0, 0.0], 20).unwrap();
+        assert_eq!(nucleus.period, 20);
+        assert_near(
+            nucleus.centre[0],
+            "-1.99999999998653547063236736429077818859381025",
+        );
+        assert!((nucleus.size / 4.8984e-23 - 1.0).abs() < 1e-3);
+        assert!(!nucleus.can_view());
+    }
+
+    #[test]
+    fn rejects_minibrots_below_double_double_precision() {
+        assert!(matches!(
+            Nucleus::find([-2.0, 0.0], 30),
+            Err(NucleusError::TooSmall)
+        ));
+        assert!(matches!(
+            Nucleus::find([0.0, 0.0], 0),
+            Err(NucleusError::NoPeriod)
+        ));
+    }
+
+    #[test]
+    fn detects_the_period_of_the_atom_domain() {
+        assert_eq!(detect_period([-1.75, 0.0], 1000), Some(3));
+        assert_eq!(detect_period([-0.12, 0.74], 1000), Some(3));
+        assert_eq!(detect_period([-1.0, 0.0], 1000), Some(2));
+    }
+}
@@ -0,0 +1,156 @@
+// The orbit z0, z1, z2, ... of a single point, computed on the CPU in f64 with the same
+// formula as mandelbrot() in shader.wgsl
+
+use crate::complex::{length, sub};
+
+// |z| at which an orbit counts as escaped, as in the shader
+const ESCAPE_RADIUS: f64 = 4.0;
+// Longest orbit kept, however high the iteration limit is set
+const MAX_POINTS: usize = 100_000;
+// Longest cycle looked for, and how close its repeats must be
+const MAX_PERIOD: usize = 1024;
+const PERIOD_TOLERANCE: f64 = 1e-9;
+
+pub struct Orbit {
+    pub c: [f64; 2],
+    pub points: Vec<[f64; 2]>,
+    // Iteration at which |z| reached ESCAPE_RADIUS, None when the orbit stayed bounded
+    pub escaped: Option<usize>,
+    // Length of the cycle the orbit settled into, if one was found
+    pub period: Option<usize>,
+    // The rest of what it was computed from, to tell whether it can be reused
+    start: [f64; 2],
+    exponent: f32,
+    value: f32,
+    iterations: i32,
+}
+
+fn step(z: [f64; 2], c: [f64; 2], exponent: f64, value: f64) -> [f64; 2] {
+    [
+        z[0].abs().powf(exponent) - z[1].abs().powf(exponent) + c[0],
+        value * z[0] * z[1] + c[1],
+    ]
+}
+
+// Smallest p for which the last p points repeat the p before them
+fn find_period(points: &[[f64; 2]]) -> Option<usize> {
+    (1..=MAX_PERIOD.min(points.len() / 2)).find(|&period| {
+        let end = points.len();
+        (end - period..end).all(|i| {
+            length(sub(points[i], points[i - period]))
+                < PERIOD_TOLERANCE * length(points[i]).max(1.0)
+        })
+    })
+}
+
+impl Orbit {
+    // Starts from zero for the Mandelbrot set, or from the point itself with c the seed for a
+    // Julia set
+    pub fn compute(
+        start: [f64; 2],
+        c: [f64; 2],
+        exponent: f32,
+        value: f32,
+        iterations: i32,
+    ) -> Self {
+        let limit = (iterations.max(0) as usize).min(MAX_POINTS);
+        let mut z = start;
+        let mut points = vec![z];
+        let mut escaped = None;
+        for iteration in 0..limit {
+            if length(z) >= ESCAPE_RADIUS {
+                escaped = Some(iteration);
+                break;
+            }
+            z = step(z, c, exponent as f64, value as f64);
+            points.push(z);
+        }
+
+        let period = match escaped {
+            Some(_) => None,
+            None => find_period(&points),
+        };
+        Self {
+            c,
+            points,
+            escaped,
+            period,
+            start,
+            exponent,
+            value,
+            iterations,
+        }
+    }
+
+    // Whether compute would return this orbit again for these arguments
+    pub fn is_of(
+        &self,
+        start: [f64; 2],
+        c: [f64; 2],
+        exponent: f32,
+        value: f32,
+        iterations: i32,
+    ) -> bool {
+        self.start == start
+            && self.c == c
+            && self.exponent == exponent
+            && self.value == value
+            && self.iterations == iterations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minus_one_cycles_between_zero_and_minus_one() {
+        let orbit = Orbit::compute([0.0, 0.0], [-1.0, 0.0], 2.0, 2.0, 100);
+        assert_eq!(orbit.escaped, None);
+        assert_eq!(orbit.period, Some(2));
+        assert_eq!(orbit.points.len(), 101);
+        for (i, &z) in orbit.points.iter().enumerate() {
+            let expected = if i % 2 == 0 { 0.0 } else { -1.0 };
+            assert_eq!(z, [expected, 0.0], "z{}", i);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_escape_radius() {
+        // 0, 1, 2, 5
+        let orbit = Orbit::compute([0.0, 0.0], [1.0, 0.0], 2.0, 2.0, 100);
+        assert_eq!(orbit.escaped, Some(3));
+        assert_eq!(orbit.period, None);
+        assert_eq!(
+            orbit.points,
+            [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [5.0, 0.0]]
+        );
+        let last = orbit.points[orbit.points.len() - 2];
+        assert!(length(last) < ESCAPE_RADIUS);
+    }
+
+    #[test]
+    fn stops_at_the_iteration_limit() {
+        let orbit = Orbit::compute([0.0, 0.0], [0.25, 0.0], 2.0, 2.0, 10);
+        assert_eq!(orbit.escaped, None);
+        assert_eq!(orbit.points.len(), 11);
+        let orbit = Orbit::compute([0.0, 0.0], [0.25, 0.0], 2.0, 2.0, i32::MAX);
+        assert_eq!(orbit.points.len(), MAX_POINTS + 1);
+    }
+
+    #[test]
+    fn starts_a_julia_orbit_at_the_point() {
+        let orbit = Orbit::compute([0.5, 0.5], [0.0, 0.0], 2.0, 2.0, 1);
+        assert_eq!(orbit.points, [[0.5, 0.5], [0.0, 0.5]]);
+    }
+
+    #[test]
+    fn knows_its_arguments() {
+        let orbit = Orbit::compute([0.0, 0.0], [-1.0, 0.0], 2.0, 2.0, 100);
+        assert!(orbit.is_of([0.0, 0.0], [-1.0, 0.0], 2.0, 2.0, 100));
+        assert!(!orbit.is_of([0.0, 0.0], [-1.0, 0.0], 2.0, 2.0, 101));
+        assert!(!orbit.is_of([0.0, 0.0], [-1.0, 1e-12], 2.0, 2.0, 100));
+        assert!(!orbit.is_of([0.0, 0.0], [-1.0, 0.0], 3.0, 2.0, 100));
+        assert!(!orbit.is_of([0.1, 0.0], [-1.0, 0.0], 2.0, 2.0, 100));
+    }
+}
@@ -0,0 +1,454 @@
+// External rays of z^2 + c. A ray with angle θ (in turns) is the set of c whose Böttcher
+// coordinate has argument θ, where z_n(c) ≈ ρ^(2^(n - 1)) e^(2πi 2^(n - 1) θ) far from the set.
+// Rays are traced inward by following that target with Newton's method while the depth n grows,
+// and angles are read outward by walking back out and noting which half-plane each z_n is in.
+
+use std::{collections::HashMap, fmt};
+
+use crate::complex::{add, length, mul, scale, sub};
+
+// |z_n| targeted at the start of each depth, the end is its square root
+const ESCAPE_RADIUS: f64 = 65536.0;
+// Newton targets per depth
+const SHARPNESS: usize = 8;
+const MAX_DEPTH: usize = 256;
+const MAX_NEWTON_STEPS: usize = 16;
+const STEP_TOLERANCE: f64 = 1e-15;
+// Multiplier steps from a component's nucleus, where it is 0, out to its root, where it is 1
+const ROOT_STEPS: usize = 16;
+const MAX_ROOT_NEWTON_STEPS: usize = 64;
+// Longest binary expansion written out before it is cut short
+const MAX_DIGITS: usize = 64;
+// Binary digits kept in an Angle, so that doubling never overflows
+const MAX_BITS: u32 = 62;
+
+// A rational angle in turns, numerator / denominator in [0, 1)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Angle {
+    numerator: u64,
+    denominator: u64,
+}
+
+pub struct Ray {
+    pub angle: Angle,
+    pub points: Vec<[f64; 2]>,
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn binary_value(digits: &str) -> Result<u64, String> {
+    digits.chars().try_fold(0u64, |value, digit| match digit {
+        '0' => Ok(value << 1),
+        '1' => Ok(value << 1 | 1),
+        _ => Err(format!("'{}' is not a binary digit", digit)),
+    })
+}
+
+impl Angle {
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        let numerator = numerator % denominator;
+        let divisor = gcd(numerator, denominator).max(1);
+        Self {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    // Reads a fraction like "1/7", or binary digits like "0.001", "0.(001)" or "0.01(10)"
+    // where the digits in parentheses repeat forever
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some((numerator, denominator)) = text.split_once('/') {
+            let numerator = numerator.trim().parse::<u64>().map_err(|e| e.to_string())?;
+            let denominator = denominator
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| e.to_string())?;
+            if denominator == 0 {
+                return Err("the denominator is zero".to_string());
+            }
+            return Ok(Self::new(numerator, denominator));
+        }
+
+        let digits = text
+            .strip_prefix("0.")
+            .or_else(|| text.strip_prefix('.'))
+            .ok_or_else(|| format!("{} is neither a fraction nor a binary angle", text))?;
+        let (preperiodic, periodic) = match digits.split_once('(') {
+            Some((preperiodic, rest)) => match rest.strip_suffix(')') {
+                Some(periodic) if !periodic.is_empty() => (preperiodic, periodic),
+                _ => return Err("the repeating digits need a closing ')'".to_string()),
+            },
+            None => (digits, ""),
+        };
+        if (preperiodic.len() + periodic.len()) as u32 > MAX_BITS {
+            return Err(format!("at most {} binary digits are supported", MAX_BITS));
+        }
+
+        // 0.a(b) = (a + b / (2^p - 1)) / 2^k for k digits in a and p in b
+        let a = binary_value(preperiodic)?;
+        let b = binary_value(periodic)?;
+        let shift = 1u64 << preperiodic.len();
+        if periodic.is_empty() {
+            Ok(Self::new(a, shift))
+        } else {
+            let repeat = (1u64 << periodic.len()) - 1;
+            Ok(Self::new(a * repeat + b, shift * repeat))
+        }
+    }
+
+    // The first `bits` digits of a binary expansion 0.d1 d2 d3 ...
+    fn from_digits(digits: &[bool]) -> Self {
+        let digits = &digits[..digits.len().min(MAX_BITS as usize)];
+        let numerator = digits
+            .iter()
+            .fold(0u64, |value, &digit| value << 1 | digit as u64);
+        Self::new(numerator, 1u64 << digits.len())
+    }
+
+    pub fn turns(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    fn double(&self) -> Self {
+        Self::new(
+            ((self.numerator as u128 * 2) % self.denominator as u128) as u64,
+            self.denominator,
+        )
+    }
+
+    // Number of doublings that bring the angle back to itself, None when it is preperiodic or
+    // its period is too long to look for
+    fn period(&self) -> Option<usize> {
+        let mut angle = self.double();
+        for period in 1..=MAX_BITS as usize {
+            if angle == *self {
+                return Some(period);
+            }
+            angle = angle.double();
+        }
+        None
+    }
+
+    // Binary expansion with the repeating part in parentheses, e.g. 0.(001) for 1/7
+    pub fn binary(&self) -> String {
+        let mut digits = String::from("0.");
+        let mut seen = HashMap::new();
+        let mut remainder = self.numerator;
+        while remainder != 0 {
+            if let Some(&start) = seen.get(&remainder) {
+                digits.insert(start, '(');
+                digits.push(')');
+                return digits;
+            }
+            if seen.len() == MAX_DIGITS {
+                digits.push('…');
+                return digits;
+            }
+            seen.insert(remainder, digits.len());
+            let doubled = remainder as u128 * 2;
+            let digit = doubled >= self.denominator as u128;
+            digits.push(if digit { '1' } else { '0' });
+            remainder = (doubled % self.denominator as u128) as u64;
+        }
+        if digits.len() == 2 {
+            digits.push('0');
+        }
+        digits
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+fn polar(radius: f64, turns: f64) -> [f64; 2] {
+    let angle = turns * std::f64::consts::TAU;
+    [radius * angle.cos(), radius * angle.sin()]
+}
+
+// z_n(c) and its derivative with respect to c, with z_1 = c
+fn iterate(c: [f64; 2], n: usize) -> ([f64; 2], [f64; 2]) {
+    let mut z = [0.0, 0.0];
+    let mut dz = [0.0, 0.0];
+    for _ in 0..n {
+        dz = add(mul(scale(z, 2.0), dz), [1.0, 0.0]);
+        z = add(mul(z, z), c);
+    }
+    (z, dz)
+}
+
+// Moves c until z_n(c) = target
+fn newton(mut c: [f64; 2], n: usize, target: [f64; 2]) -> Option<[f64; 2]> {
+    for _ in 0..MAX_NEWTON_STEPS {
+        let (z, dz) = iterate(c, n);
+        let step = crate::complex::div(sub(z, target), dz);
+        if !step[0].is_finite() || !step[1].is_finite() {
+            return None;
+        }
+        c = sub(c, step);
+        if length(step) <= STEP_TOLERANCE * length(c).max(1.0) {
+            break;
+        }
+    }
+    Some(c)
+}
+
+// Moves z and c until z is a point of a cycle of length `period` with the given multiplier
+fn newton_cycle(
+    mut z: [f64; 2],
+    mut c: [f64; 2],
+    period: usize,
+    multiplier: f64,
+    steps: usize,
+) -> ([f64; 2], [f64; 2]) {
+    let one = [1.0, 0.0];
+    for _ in 0..steps {
+        // w = f^period(z) and its first and second derivatives with respect to z and c
+        let (mut w, mut w_z, mut w_c, mut w_zz, mut w_zc) =
+            (z, one, [0.0, 0.0], [0.0; 2], [0.0; 2]);
+        for _ in 0..period {
+            w_zz = scale(add(mul(w_z, w_z), mul(w, w_zz)), 2.0);
+            w_zc = scale(add(mul(w_z, w_c), mul(w, w_zc)), 2.0);
+            w_z = scale(mul(w, w_z), 2.0);
+            w_c = add(scale(mul(w, w_c), 2.0), one);
+            w = add(mul(w, w), c);
+        }
+        // Solves the 2x2 system for w - z = 0 and w_z - multiplier = 0 by Cramer's rule
+        let (g1, g2) = (sub(w, z), sub(w_z, [multiplier, 0.0]));
+        let (j11, j12, j21, j22) = (sub(w_z, one), w_c, w_zz, w_zc);
+        let determinant = sub(mul(j11, j22), mul(j12, j21));
+        let dz = crate::complex::div(sub(mul(j22, g1), mul(j12, g2)), determinant);
+        let dc = crate::complex::div(sub(mul(j11, g2), mul(j21, g1)), determinant);
+        if !dz.iter().chain(&dc).all(|part| part.is_finite()) {
+            break;
+        }
+        z = sub(z, dz);
+        c = sub(c, dc);
+        if length(dz) + length(dc) <= STEP_TOLERANCE * length(c).max(1.0) {
+            break;
+        }
+    }
+    (z, c)
+}
+
+// A periodic ray lands on the root of a hyperbolic component of the same period. Rays creep into
+// such roots too slowly to trace, so the root of the component whose nucleus is nearest the end
+// of the trace is found instead, and only kept when it is nearer still.
+fn landing_point(angle: Angle, end: [f64; 2]) -> Option<[f64; 2]> {
+    let period = angle.period()?;
+    let nucleus = newton(end, period, [0.0, 0.0])?;
+    let (mut z, mut c) = ([0.0, 0.0], nucleus);
+    for step in 1..=ROOT_STEPS {
+        let multiplier = step as f64 / ROOT_STEPS as f64;
+        (z, c) = newton_cycle(z, c, period, multiplier, MAX_NEWTON_STEPS);
+    }
+    // The cycle meets its parent's at a satellite root, where Newton slows down
+    let (_, root) = newton_cycle(z, c, period, 1.0, MAX_ROOT_NEWTON_STEPS);
+    let finite = root.iter().all(|part| part.is_finite());
+    (finite && length(sub(root, end)) < length(sub(nucleus, end))).then_some(root)
+}
+
+impl Ray {
+    // Traces the ray inward from the escape radius until it stops moving at f64 precision, then
+    // ends it on its landing point when that is a root it can find
+    pub fn trace(angle: Angle) -> Self {
+        let mut c = polar(ESCAPE_RADIUS, angle.turns());
+        let mut points = vec![c];
+        let mut phase = angle;
+        'depth: for n in 1..=MAX_DEPTH {
+            let depth_start = c;
+            for k in 1..=SHARPNESS {
+                let radius = ESCAPE_RADIUS.powf(0.5f64.powf(k as f64 / SHARPNESS as f64));
+                match newton(c, n, polar(radius, phase.turns())) {
+                    Some(next) => c = next,
+                    None => break 'depth,
+                }
+                points.push(c);
+            }
+            if length(sub(c, depth_start)) <= STEP_TOLERANCE * length(c).max(1.0) {
+                break;
+            }
+            phase = phase.double();
+        }
+        if let Some(root) = landing_point(angle, c) {
+            points.push(root);
+        }
+        Self { angle, points }
+    }
+}
+
+// Reads the binary digits of the external angle through c by walking out along its ray. The
+// digits are only known for as many iterations as c takes to escape, so points closer to the set
+// give longer expansions. Returns None for points that do not escape.
+pub fn estimate_angle(c: [f64; 2], iterations: i32) -> Option<Vec<bool>> {
+    let inner_radius = ESCAPE_RADIUS.sqrt();
+    let mut z = [0.0, 0.0];
+    let mut depth = (1..=iterations.max(1) as usize).find(|_| {
+        z = add(mul(z, z), c);
+        length(z) >= inner_radius
+    })?;
+
+    let mut c = c;
+    let mut digits = Vec::with_capacity(depth);
+    loop {
+        // arg z_n is 2^(n - 1) θ, in the lower half-plane (or on the negative real axis) exactly
+        // when digit n of θ is 1
+        let (mut z, _) = iterate(c, depth);
+        digits.push(z[1] < 0.0 || (z[1] == 0.0 && z[0] < 0.0));
+        if depth == 1 {
+            break;
+        }
+        // Follows the targets rather than |z_n| itself, which rounding keeps just short of the
+        // escape radius
+        let mut radius = length(z);
+        while radius < ESCAPE_RADIUS {
+            let next = radius
+                .powf(2f64.powf(1.0 / SHARPNESS as f64))
+                .min(ESCAPE_RADIUS);
+            c = newton(c, depth, scale(z, next / length(z)))?;
+            z = iterate(c, depth).0;
+            radius = next;
+        }
+        depth -= 1;
+    }
+    digits.reverse();
+    Some(digits)
+}
+
+// Binary digits as text, cut short after MAX_DIGITS, with the angle they describe
+fn describe(digits: &[bool]) -> (String, Angle) {
+    let mut text = String::from("0.");
+    text.extend(
+        digits
+            .iter()
+            .take(MAX_DIGITS)
+            .map(|&digit| if digit { '1' } else { '0' }),
+    );
+    if digits.len() > MAX_DIGITS {
+        text.push('…');
+    }
+    (text, Angle::from_digits(digits))
+}
+
+// External angles of the rays passing near c: the ray through c itself when it escapes, or
+// those through points on circles of growing radius around it otherwise
+pub fn angles_near(c: [f64; 2], radius: f64, iterations: i32) -> Vec<(String, Angle)> {
+    const SAMPLES: usize = 16;
+    const CIRCLES: usize = 4;
+
+    if let Some(digits) = estimate_angle(c, iterations) {
+        return vec![describe(&digits)];
+    }
+
+    let mut found: Vec<(String, Angle)> = Vec::new();
+    for circle in 1..=CIRCLES {
+        for sample in 0..SAMPLES {
+            let point = add(
+                c,
+                polar(radius * circle as f64, sample as f64 / SAMPLES as f64),
+            );
+            if let Some(digits) = estimate_angle(point, iterations) {
+                let estimate = describe(&digits);
+                if !found.iter().any(|(text, _)| *text == estimate.0) {
+                    found.push(estimate);
+                }
+            }
+        }
+        if !found.is_empty() {
+            break;
+        }
+    }
+    found.sort_by(|a, b| a.1.turns().total_cmp(&b.1.turns()));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_lands(angle: Angle, expected: [f64; 2]) {
+        let landing = *Ray::trace(angle).points.last().unwrap();
+        assert!(
+            length(sub(landing, expected)) < 1e-6,
+            "the {angle} ray ends at {landing:?}"
+        );
+    }
+
+    #[test]
+    fn parses_fractions_and_binary() {
+        assert_eq!(Angle::parse("1/7"), Ok(Angle::new(1, 7)));
+        assert_eq!(Angle::parse("3/6"), Ok(Angle::new(1, 2)));
+        assert_eq!(Angle::parse("0.(001)"), Ok(Angle::new(1, 7)));
+        assert_eq!(Angle::parse(".01(10)"), Ok(Angle::new(5, 12)));
+        assert_eq!(Angle::parse("0.1"), Ok(Angle::new(1, 2)));
+    }
+
+    #[test]
+    fn rejects_malformed_angles() {
+        assert!(Angle::parse("1/0").is_err());
+        assert!(Angle::parse("0.()").is_err());
+        assert!(Angle::parse("0.(01").is_err());
+        assert!(Angle::parse("0.012").is_err());
+        assert!(Angle::parse("1.5").is_err());
+    }
+
+    #[test]
+    fn parses_up_to_62_digits() {
+        let ones = "1".repeat(62);
+        let expected = Angle::new((1 << 62) - 1, 1 << 62);
+        assert_eq!(Angle::parse(&format!("0.{ones}")), Ok(expected));
+        let mixed = format!("0.{}({})", "1".repeat(31), "01".repeat(15) + "1");
+        assert!(Angle::parse(&mixed).is_ok());
+        assert!(Angle::parse(&format!("0.{ones}1")).is_err());
+        assert!(Angle::parse(&format!("0.({ones}1)")).is_err());
+    }
+
+    #[test]
+    fn writes_binary_that_parses_back() {
+        assert_eq!(Angle::new(1, 7).binary(), "0.(001)");
+        assert_eq!(Angle::new(1, 4).binary(), "0.01");
+        assert_eq!(Angle::new(0, 1).binary(), "0.0");
+        for angle in [
+            Angle::new(1, 7),
+            Angle::new(5, 12),
+            Angle::new(1, 3),
+            Angle::new(9, 56),
+        ] {
+            assert_eq!(Angle::parse(&angle.binary()), Ok(angle));
+        }
+    }
+
+    #[test]
+    fn rays_land_on_their_roots() {
+        // 1/3 and 2/3 land on the root of the period 2 bulb, 1/7 and 2/7 on the rabbit's
+        assert_lands(Angle::new(1, 3), [-0.75, 0.0]);
+        assert_lands(Angle::new(2, 3), [-0.75, 0.0]);
+        assert_lands(Angle::new(1, 7), [-0.125, 0.649519052838329]);
+        assert_lands(Angle::new(2, 7), [-0.125, 0.649519052838329]);
+        // The cusp, a primitive root, and the period 4 bulb's root at internal angle 1/4
+        assert_lands(Angle::new(0, 1), [0.25, 0.0]);
+        assert_lands(Angle::new(3, 7), [-1.75, 0.0]);
+        assert_lands(Angle::new(1, 15), [0.25, 0.5]);
+        // Preperiodic rays land on Misiurewicz points without help
+        assert_lands(Angle::new(1, 6), [0.0, 1.0]);
+    }
+
+    #[test]
+    fn finds_the_period_under_doubling() {
+        assert_eq!(Angle::new(0, 1).period(), Some(1));
+        assert_eq!(Angle::new(1, 3).period(), Some(2));
+        assert_eq!(Angle::new(2, 7).period(), Some(3));
+        assert_eq!(Angle::new(1, 31).period(), Some(5));
+        assert_eq!(Angle::new(1, 6).period(), None);
+        assert_eq!(Angle::new(1, 4).period(), None);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
+// States of a requested mapping, set by the map_async callback
+const PENDING: u8 = 0;
+const MAPPED: u8 = 1;
+const FAILED: u8 = 2;
+
+// Copies a small GPU buffer back to the CPU without stalling the frame. Only one copy is in
+// flight at a time, a new one can be encoded once the last has been read.
+pub struct Readback {
+    buffer: wgpu::Buffer,
+    size: u64,
+    // A copy has been encoded but not yet requested for reading
+    encoded: bool,
+    // buffer is being mapped, no new copies are taken until it has been read
+    mapping: bool,
+    state: Arc<AtomicU8>,
+}
+
+impl Readback {
+    pub fn new(device: &wgpu::Device, size: u64, label: &str) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            buffer,
+            size,
+            encoded: false,
+            mapping: false,
+            state: Arc::new(AtomicU8::new(PENDING)),
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        !self.encoded && !self.mapping
+    }
+
+    pub fn copy_from(&mut self, encoder: &mut wgpu::CommandEncoder, source: &wgpu::Buffer) {
+        encoder.copy_buffer_to_buffer(source, 0, &self.buffer, 0, self.size);
+        self.encoded = true;
+    }
+
+    // Call after the encoder passed to copy_from has been submitted
+    pub fn request(&mut self) {
+        if self.encoded {
+            let state = self.state.clone();
+            state.store(PENDING, Ordering::Release);
+            self.buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let mapped = match result {
+                        Ok(()) => MAPPED,
+                        Err(e) => {
+                            log::error!("could not read back a GPU buffer: {}", e);
+                            FAILED
+                        }
+                    };
+                    state.store(mapped, Ordering::Release);
+                });
+            self.encoded = false;
+            self.mapping = true;
+        }
+    }
+
+    // Returns the copied bytes once they have arrived
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<Vec<u8>> {
+        if !self.mapping {
+            return None;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        match self.state.swap(PENDING, Ordering::AcqRel) {
+            MAPPED => {}
+            FAILED => {
+                // A failed mapping usually leaves the buffer unmapped already, so the error
+                // from unmapping it again is expected and dropped
+                device.push_error_scope(wgpu::ErrorFilter::Validation);
+                self.buffer.unmap();
+                drop(device.pop_error_scope());
+                self.mapping = false;
+                return None;
+            }
+            _ => return None,
+        }
+
+        let data = self.buffer.slice(..).get_mapped_range().to_vec();
+        self.buffer.unmap();
+        self.mapping = false;
+        Some(data)
+    }
+}
@@ -0,0 +1,844 @@
+use std::{fmt, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::Viewport,
+    export::{self, ImageFormat},
+    fractal::FractalParams,
+    scene::Colouring,
+    view::View,
+};
+
+// Widest tile rendered at once by image exports, further limited by the device
+const MAX_TILE_WIDTH: u32 = 2048;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Mouse {
+    //            align(16) size(32)
+    pub x: f32,        // offset(0)  align(4)  size(4)
+    pub y: f32,        // offset(4)  align(4)  size(4)
+    pub drag: i32,     // offset(8)  align(4)  size(4)
+    pub px: f32,       // offset(12) align(4)  size(4)
+    pub py: f32,       // offset(16) align(4)  size(4)
+    pub centre_x: f32, // offset(20) align(4)  size(4)
+    pub centre_y: f32, // offset(24) align(4)  size(4)
+    _pad: u32,         // offset(28)           size(4)
+}
+
+impl Mouse {
+    pub fn new() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            drag: -1,
+            px: 0.0,
+            py: 0.0,
+            centre_x: -0.765,
+            centre_y: 0.0,
+            _pad: 0,
+        }
+    }
+
+    pub fn set_drag(&mut self) {
+        self.drag = 1;
+    }
+
+    pub fn unset_drag(&mut self) {
+        self.drag = 0;
+    }
+
+    pub fn update_position(&mut self, x: f32, y: f32) {
+        self.px = self.x;
+        self.py = self.y;
+        self.x = -x;
+        self.y = -y;
+    }
+
+    // Cursor position in window pixels
+    pub fn position(&self) -> [f32; 2] {
+        [-self.x, -self.y]
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShaderUniform {
+    //            align(16) size(96)
+    pub resolution: [f32; 2],   // offset(0)  align(8)  size(8)
+    pub iterations: i32,        // offset(8)  align(4)  size(4)
+    pub value: f32,             // offset(12) align(4)  size(4)
+    pub mouse: Mouse,           // offset(16) align(16) size(32)
+    pub axis_range: f32,        // offset(48) align(4)  size(4)
+    pub exponent: f32,          // offset(52) align(4)  size(4)
+    pub palette: i32,           // offset(56) align(4)  size(4)
+    pub palette_offset: f32,    // offset(60) align(4)  size(4)
+    pub palette_density: f32,   // offset(64) align(4)  size(4)
+    pub count_unresolved: i32,  // offset(68) align(4)  size(4)
+    pub pixel_offset: [f32; 2], // offset(72) align(8)  size(8)
+    pub seed: [f32; 2],         // offset(80) align(8)  size(8)
+    pub julia: i32,             // offset(88) align(4)  size(4)
+    _pad: u32,                  // offset(92)           size(4)
+}
+
+impl ShaderUniform {
+    pub fn new() -> Self {
+        Self {
+            resolution: [0.0, 0.0],
+            iterations: 100,
+            value: 2.0,
+            mouse: Mouse::new(),
+            axis_range: 2.0,
+            exponent: 2.0,
+            palette: 0,
+            palette_offset: 0.0,
+            palette_density: 1.0,
+            count_unresolved: 0,
+            pixel_offset: [0.0, 0.0],
+            seed: [0.0, 0.0],
+            julia: 0,
+            _pad: 0,
+        }
+    }
+
+    pub fn from_settings(viewport: &Viewport, params: &FractalParams) -> Self {
+        let mut uniform = Self::new();
+        uniform.update_viewport(viewport);
+        uniform.update_params(params);
+        uniform
+    }
+
+    pub fn update_resolution(&mut self, width: f32, height: f32) {
+        self.resolution = [width, height];
+    }
+
+    pub fn update_iterations(&mut self, iterations: i32) {
+        self.iterations = iterations;
+    }
+
+    pub fn update_value(&mut self, value: f32) {
+        self.value = value;
+    }
+
+    pub fn update_exponent(&mut self, exponent: f32) {
+        self.exponent = exponent;
+    }
+
+    pub fn update_view(&mut self, view: &View) {
+        self.mouse.centre_x = view.centre[0] as f32;
+        self.mouse.centre_y = view.centre[1] as f32;
+        self.axis_range = view.axis_range as f32;
+        self.iterations = view.iterations;
+        self.value = view.value;
+        self.exponent = view.exponent;
+        self.update_julia(view.julia, view.seed);
+    }
+
+    pub fn update_julia(&mut self, julia: bool, seed: [f64; 2]) {
+        self.julia = julia as i32;
+        self.seed = [seed[0] as f32, seed[1] as f32];
+    }
+
+    pub fn update_colouring(&mut self, colouring: &Colouring) {
+        self.palette = colouring.palette.index();
+        self.palette_offset = colouring.offset;
+        self.palette_density = colouring.density;
+    }
+
+    pub fn update_viewport(&mut self, viewport: &Viewport) {
+        self.resolution = [viewport.width as f32, viewport.height as f32];
+        self.mouse.centre_x = viewport.centre[0] as f32;
+        self.mouse.centre_y = viewport.centre[1] as f32;
+        self.axis_range = viewport.axis_range as f32;
+    }
+
+    pub fn update_params(&mut self, params: &FractalParams) {
+        self.iterations = params.iterations;
+        self.value = params.value;
+        self.exponent = params.exponent;
+        self.update_julia(params.julia, params.seed);
+        self.update_colouring(&params.colouring);
+    }
+
+    // A copy with only what changes the data pass, so the cursor and colouring are left out
+    pub fn data_key(&self) -> Self {
+        Self {
+            mouse: Mouse {
+                x: 0.0,
+                y: 0.0,
+                drag: 0,
+                px: 0.0,
+                py: 0.0,
+                ..self.mouse
+            },
+            palette: 0,
+            palette_offset: 0.0,
+            palette_density: 0.0,
+            ..*self
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 3],
+    colour: [f32; 3],
+}
+
+impl Vertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-1.0, 1.0, 0.0],
+        colour: [1.0, 0.0, 0.0],
+    },
+    Vertex {
+        position: [-1.0, -1.0, 0.0],
+        colour: [0.0, 1.0, 0.0],
+    },
+    Vertex {
+        position: [1.0, -1.0, 0.0],
+        colour: [0.0, 0.0, 1.0],
+    },
+    Vertex {
+        position: [1.0, 1.0, 0.0],
+        colour: [0.3, 0.4, 0.3],
+    },
+];
+
+const INDICES: &[u16] = &[0, 1, 3, 1, 2, 3];
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    entry_point: &str,
+    label: &str,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                // Every pixel is overwritten, and float targets cannot blend
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+// Float target the window's data pass writes, and the bind group fs_colour reads it through
+fn create_data_target(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    width: u32,
+    height: u32,
+) -> (wgpu::TextureView, wgpu::BindGroup) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Data Target"),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: export::FLOAT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::TextureView(&view),
+        }],
+        label: Some("data_bind_group"),
+    });
+    (view, bind_group)
+}
+
+/// Why the renderer, or the window it draws to, could not be set up
+#[derive(Debug)]
+pub enum SetupError {
+    /// The window could not be opened
+    Window(winit::error::OsError),
+    /// A surface could not be created for the window
+    Surface(wgpu::CreateSurfaceError),
+    /// The adapter offers no format the window can be drawn in
+    UnsupportedSurface,
+    /// No graphics adapter was found, not even the fallback one
+    NoAdapter,
+    /// The adapter was found but its device could not be started
+    Device(wgpu::RequestDeviceError),
+    /// The shader or its pipelines failed validation, with wgpu's description of why
+    Shader(String),
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::Window(e) => write!(f, "could not open a window: {}", e),
+            SetupError::Surface(e) => write!(f, "could not draw to the window: {}", e),
+            SetupError::UnsupportedSurface => {
+                write!(f, "the graphics adapter cannot draw to the window")
+            }
+            SetupError::NoAdapter => write!(
+                f,
+                "no graphics adapter was found, check that drivers for Vulkan, Metal, DirectX 12 \
+                 or OpenGL are installed"
+            ),
+            SetupError::Device(e) => write!(f, "could not start the graphics device: {}", e),
+            SetupError::Shader(e) => write!(f, "could not compile the shader: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SetupError {}
+
+impl From<winit::error::OsError> for SetupError {
+    fn from(e: winit::error::OsError) -> Self {
+        SetupError::Window(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for SetupError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        SetupError::Surface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for SetupError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        SetupError::Device(e)
+    }
+}
+
+/// Why a render could not be made
+#[derive(Debug)]
+pub enum RenderError {
+    /// A texture side is zero or above the device's limit, see [`Renderer::max_texture_size`]
+    InvalidSize { width: u32, height: u32, max: u32 },
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::InvalidSize { width, height, max } => write!(
+                f,
+                "cannot render {}x{} to a texture, both sides must be from 1 to {}",
+                width, height, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// The GPU side of the explorer: the device, the shader's pipelines and the buffers they draw
+/// with. It renders the fractal offscreen on its own, the window adds its surface on top.
+pub struct Renderer {
+    // Shared with the program that created them, when it did
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    shader: wgpu::ShaderModule,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    data_bind_group_layout: wgpu::BindGroupLayout,
+    colour_pipeline_layout: wgpu::PipelineLayout,
+    // Raw iteration data, for the window's data pass and data exports
+    data_pipeline: wgpu::RenderPipeline,
+    image_pipeline: wgpu::RenderPipeline,
+    // Linear colour for 16-bit PNGs
+    float_pipeline: wgpu::RenderPipeline,
+    // Offscreen renders go through their own uniform so the window's is untouched
+    offscreen_buffer: wgpu::Buffer,
+    offscreen_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    num_indices: u32,
+}
+
+impl Renderer {
+    /// Renders offscreen on the best adapter available, with no window
+    pub async fn new() -> Result<Self, SetupError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+        let (_, renderer) = Self::request(&instance, None).await?;
+        Ok(renderer)
+    }
+
+    // Starts on the best adapter that can draw to `surface`, falling back to the fallback
+    // adapter, usually a software renderer, when there is none or it fails to start
+    pub(crate) async fn request(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+    ) -> Result<(wgpu::Adapter, Self), SetupError> {
+        match Self::request_on(instance, surface, false).await {
+            Ok(started) => Ok(started),
+            Err(e) => {
+                log::warn!("{}, trying the fallback adapter", e);
+                Self::request_on(instance, surface, true)
+                    .await
+                    .map_err(|fallback| match fallback {
+                        // Without a fallback adapter the first failure says more
+                        SetupError::NoAdapter => e,
+                        fallback => fallback,
+                    })
+            }
+        }
+    }
+
+    async fn request_on(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+        force_fallback_adapter: bool,
+    ) -> Result<(wgpu::Adapter, Self), SetupError> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: surface,
+                force_fallback_adapter,
+            })
+            .await
+            .ok_or(SetupError::NoAdapter)?;
+        let renderer = Self::from_adapter(&adapter).await?;
+        Ok((adapter, renderer))
+    }
+
+    /// Starts a new device on `adapter`
+    pub async fn from_adapter(adapter: &wgpu::Adapter) -> Result<Self, SetupError> {
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    // Timestamps are only used for the frame time overlay, when available
+                    features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await?;
+        Self::from_device(Arc::new(device), Arc::new(queue)).await
+    }
+
+    /// Renders with a device and queue the program already has, which it keeps using alongside
+    /// the renderer. Only the default limits are needed.
+    pub async fn from_device(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+    ) -> Result<Self, SetupError> {
+        // Shader and pipeline errors would otherwise only reach wgpu's panicking error handler
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("shader_bind_group_layout"),
+            });
+        let data_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+                label: Some("data_bind_group_layout"),
+            });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Render Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let colour_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Colour Pipeline Layout"),
+                bind_group_layouts: &[&uniform_bind_group_layout, &data_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let data_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            export::FLOAT_FORMAT,
+            "fs_data",
+            "Data Pipeline",
+        );
+        let image_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            export::IMAGE_FORMAT,
+            "fs_main",
+            "Image Pipeline",
+        );
+        let float_pipeline = create_render_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            export::FLOAT_FORMAT,
+            "fs_main",
+            "Float Pipeline",
+        );
+
+        if let Some(e) = device.pop_error_scope().await {
+            return Err(SetupError::Shader(match e {
+                wgpu::Error::Validation { description, .. } => description,
+                e => e.to_string(),
+            }));
+        }
+
+        let offscreen_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("offscreen uniform"),
+            contents: bytemuck::cast_slice(&[ShaderUniform::new()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // Offscreen renders never count unresolved pixels, this is only bound to fill the layout
+        let counter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen counter"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let offscreen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: offscreen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: counter_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("offscreen_bind_group"),
+        });
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            shader,
+            uniform_bind_group_layout,
+            data_bind_group_layout,
+            colour_pipeline_layout,
+            data_pipeline,
+            image_pipeline,
+            float_pipeline,
+            offscreen_buffer,
+            offscreen_bind_group,
+            vertex_buffer,
+            index_buffer,
+            num_indices: INDICES.len() as u32,
+        })
+    }
+
+    /// The device textures from [`Renderer::render_to_texture`] belong to
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    /// The queue renders are submitted on
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Largest image [`Renderer::render_to_texture`] can produce along either side
+    pub fn max_texture_size(&self) -> u32 {
+        self.device.limits().max_texture_dimension_2d
+    }
+
+    /// Renders the viewport in one pass to a new Rgba8UnormSrgb texture, which can also be
+    /// sampled or copied from. Fails when a side is zero or above
+    /// [`Renderer::max_texture_size`], [`Renderer::render_to_image`] has no such limit.
+    pub fn render_to_texture(
+        &self,
+        viewport: &Viewport,
+        params: &FractalParams,
+    ) -> Result<wgpu::Texture, RenderError> {
+        let (width, height) = (viewport.width, viewport.height);
+        let max = self.max_texture_size();
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(RenderError::InvalidSize { width, height, max });
+        }
+        let uniform = ShaderUniform::from_settings(viewport, params);
+        Ok(self.draw(&uniform, [0, 0], [width, height], ImageFormat::Png8))
+    }
+
+    /// Renders the viewport as tightly packed RGBA8 rows in sRGB, in tiles so it can be larger
+    /// than the device's texture limit
+    pub fn render_to_image(&self, viewport: &Viewport, params: &FractalParams) -> Vec<u8> {
+        let uniform = ShaderUniform::from_settings(viewport, params);
+        let (width, height) = (viewport.width, viewport.height);
+        let tile = self.max_tile_width();
+        let row_bytes = width as usize * 4;
+        let mut pixels = vec![0; row_bytes * height as usize];
+        for y in (0..height).step_by(tile as usize) {
+            for x in (0..width).step_by(tile as usize) {
+                let size = [tile.min(width - x), tile.min(height - y)];
+                let tile_pixels = self.render_tile(&uniform, [x, y], size, ImageFormat::Png8);
+                for (row, tile_row) in tile_pixels.chunks(size[0] as usize * 4).enumerate() {
+                    let start = (y as usize + row) * row_bytes + x as usize * 4;
+                    pixels[start..start + tile_row.len()].copy_from_slice(tile_row);
+                }
+            }
+        }
+        pixels
+    }
+
+    pub(crate) fn max_tile_width(&self) -> u32 {
+        self.max_texture_size().min(MAX_TILE_WIDTH)
+    }
+
+    // Renders the `size` pixels at `offset` of the image described by `uniform`, laid out for
+    // `format`
+    pub(crate) fn render_tile(
+        &self,
+        uniform: &ShaderUniform,
+        offset: [u32; 2],
+        size: [u32; 2],
+        format: ImageFormat,
+    ) -> Vec<u8> {
+        let texture = self.draw(uniform, offset, size, format);
+        let pixels = export::read_texture(&self.device, &self.queue, &texture);
+        match format {
+            ImageFormat::Png16 => export::to_png16(&pixels),
+            _ => pixels,
+        }
+    }
+
+    fn draw(
+        &self,
+        uniform: &ShaderUniform,
+        offset: [u32; 2],
+        size: [u32; 2],
+        format: ImageFormat,
+    ) -> wgpu::Texture {
+        let (pipeline, texture_format) = match format {
+            ImageFormat::Png8 => (&self.image_pipeline, export::IMAGE_FORMAT),
+            ImageFormat::Png16 => (&self.float_pipeline, export::FLOAT_FORMAT),
+            ImageFormat::ExrData => (&self.data_pipeline, export::FLOAT_FORMAT),
+        };
+        let uniform = &ShaderUniform {
+            count_unresolved: 0,
+            pixel_offset: [offset[0] as f32, offset[1] as f32],
+            ..*uniform
+        };
+        self.queue
+            .write_buffer(&self.offscreen_buffer, 0, bytemuck::cast_slice(&[*uniform]));
+
+        let texture = export::create_target(&self.device, size[0], size[1], texture_format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Offscreen Encoder"),
+            });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Offscreen Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &self.offscreen_bind_group, &[]);
+            self.draw_quad(&mut render_pass);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        texture
+    }
+
+    // Covers the target with the full screen quad, once the pipeline and bind groups are set
+    pub(crate) fn draw_quad<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+
+    // Binds a uniform buffer and an unresolved pixel counter for the window's passes
+    pub(crate) fn create_uniform_bind_group(
+        &self,
+        uniform: &wgpu::Buffer,
+        counter: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.uniform_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: counter.as_entire_binding(),
+                },
+            ],
+            label: Some("shader_bind_group"),
+        })
+    }
+
+    // Colours a `format` target from a data target, for the window
+    pub(crate) fn create_colour_pipeline(
+        &self,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        create_render_pipeline(
+            &self.device,
+            &self.colour_pipeline_layout,
+            &self.shader,
+            format,
+            "fs_colour",
+            "Colour Pipeline",
+        )
+    }
+
+    pub(crate) fn data_pipeline(&self) -> &wgpu::RenderPipeline {
+        &self.data_pipeline
+    }
+
+    pub(crate) fn create_data_target(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::TextureView, wgpu::BindGroup) {
+        create_data_target(&self.device, &self.data_bind_group_layout, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Red channel of the middle pixel of a small render around 0, or None without a GPU
+    fn centre_pixel(julia: bool, seed: [f64; 2]) -> Option<u8> {
+        let renderer = match pollster::block_on(Renderer::new()) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                eprintln!("skipped, no renderer: {}", e);
+                return None;
+            }
+        };
+        let viewport = Viewport {
+            centre: [0.0, 0.0],
+            axis_range: 3.0,
+            width: 64,
+            height: 64,
+        };
+        let params = FractalParams {
+            julia,
+            seed,
+            ..FractalParams::default()
+        };
+        let pixels = renderer.render_to_image(&viewport, &params);
+        Some(pixels[(32 * 64 + 32) * 4])
+    }
+
+    #[test]
+    fn renders_the_julia_set_of_the_seed() {
+        // 0 lies inside the Julia set of 0, the unit disc, and escapes for a seed of 0.5
+        let Some(inside) = centre_pixel(true, [0.0, 0.0]) else {
+            return;
+        };
+        assert_eq!(inside, 0);
+        assert_ne!(centre_pixel(true, [0.5, 0.0]), Some(0));
+        // The seed is ignored for the Mandelbrot set, where c = 0 is inside
+        assert_eq!(centre_pixel(false, [0.5, 0.0]), Some(0));
+    }
+}
@@ -0,0 +1,496 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::Camera, view::View};
+
+// Bumped whenever a field changes meaning, older files are still read
+pub const VERSION: u32 = 1;
+
+// Everything needed to reproduce a render. This is the on-disk description, ShaderUniform is
+// only the GPU-facing copy built from it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub version: u32,
+    #[serde(default)]
+    pub view: ViewSettings,
+    #[serde(default)]
+    pub formula: Formula,
+    #[serde(default)]
+    pub colouring: Colouring,
+    #[serde(default)]
+    pub render: RenderSettings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettings {
+    pub centre: [f64; 2],
+    pub axis_range: f64,
+}
+
+// z = (|x|^exponent - |y|^exponent, value * x * y) + c, where c is the point, or the seed for
+// a Julia set
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Formula {
+    pub exponent: f32,
+    pub value: f32,
+    pub julia: bool,
+    pub seed: [f64; 2],
+}
+
+/// How escape times are turned into colours, points inside the set are always black
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colouring {
+    pub palette: Palette,
+    /// Shifts the gradient, one whole unit is a full cycle
+    pub offset: f32,
+    /// Number of times the gradient repeats between zero and the iteration limit
+    pub density: f32,
+}
+
+/// The colour gradients the shader offers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    Greyscale,
+    Fire,
+    Ocean,
+    Rainbow,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub iterations: i32,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            view: ViewSettings::default(),
+            formula: Formula::default(),
+            colouring: Colouring::default(),
+            render: RenderSettings::default(),
+        }
+    }
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            centre: Camera::DEFAULT_CENTRE,
+            axis_range: Camera::DEFAULT_RANGE,
+        }
+    }
+}
+
+impl Formula {
+    // The classic z = z^2 + c, which the analytic tools and other programs understand
+    pub fn is_standard(&self) -> bool {
+        self.exponent == 2.0 && self.value == 2.0
+    }
+}
+
+impl Default for Formula {
+    fn default() -> Self {
+        Self {
+            exponent: 2.0,
+            value: 2.0,
+            julia: false,
+            seed: [0.0, 0.0],
+        }
+    }
+}
+
+impl Default for Colouring {
+    fn default() -> Self {
+        Self {
+            palette: Palette::Greyscale,
+            offset: 0.0,
+            density: 1.0,
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self { iterations: 500 }
+    }
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [
+        Palette::Greyscale,
+        Palette::Fire,
+        Palette::Ocean,
+        Palette::Rainbow,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Greyscale => "Greyscale",
+            Palette::Fire => "Fire",
+            Palette::Ocean => "Ocean",
+            Palette::Rainbow => "Rainbow",
+        }
+    }
+
+    // Matches the switch in palette() in shader.wgsl
+    pub fn index(&self) -> i32 {
+        match self {
+            Palette::Greyscale => 0,
+            Palette::Fire => 1,
+            Palette::Ocean => 2,
+            Palette::Rainbow => 3,
+        }
+    }
+
+    pub fn from_index(index: i32) -> Option<Palette> {
+        Self::ALL
+            .into_iter()
+            .find(|palette| palette.index() == index)
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Toml(String),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    // A location file that could not be understood
+    Format(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Toml(e) => write!(f, "invalid TOML: {}", e),
+            SceneError::Json(e) => write!(f, "invalid JSON: {}", e),
+            SceneError::UnsupportedVersion(version) => write!(
+                f,
+                "scene version {} is newer than the supported version {}",
+                version, VERSION
+            ),
+            SceneError::Format(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(e: std::io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneError::Json(e)
+    }
+}
+
+// A scene read from another program's format, with everything that did not carry over
+pub struct Imported {
+    pub scene: Scene,
+    pub warnings: Vec<String>,
+}
+
+fn has_extension(path: &Path, expected: &str) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(expected))
+}
+
+// Files ending in .json are JSON, everything else is TOML
+fn is_json(path: &Path) -> bool {
+    has_extension(path, "json")
+}
+
+// Parses a decimal coordinate, warning when it carries more digits than an f64 keeps
+pub fn parse_precise(field: &str, text: &str, warnings: &mut Vec<String>) -> Option<f64> {
+    let value = text.trim().parse::<f64>().ok()?;
+    let mantissa = text
+        .trim()
+        .trim_start_matches(['+', '-'])
+        .split(['e', 'E'])
+        .next()
+        .unwrap_or_default();
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let significant = digits.trim_start_matches('0').trim_end_matches('0').len();
+    if significant > 17 {
+        warnings.push(format!(
+            "{} has {} significant digits, only about 17 are kept",
+            field, significant
+        ));
+    }
+    Some(value)
+}
+
+impl Scene {
+    pub fn from_view(view: &View, colouring: Colouring) -> Self {
+        Self {
+            version: VERSION,
+            view: ViewSettings {
+                centre: view.centre,
+                axis_range: view.axis_range,
+            },
+            formula: Formula {
+                exponent: view.exponent,
+                value: view.value,
+                julia: view.julia,
+                seed: view.seed,
+            },
+            colouring,
+            render: RenderSettings {
+                iterations: view.iterations,
+            },
+        }
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            centre: self.view.centre,
+            axis_range: self.view.axis_range,
+            iterations: self.render.iterations,
+            value: self.formula.value,
+            exponent: self.formula.exponent,
+            julia: self.formula.julia,
+            seed: self.formula.seed,
+        }
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, SceneError> {
+        let scene: Scene = toml::from_str(contents).map_err(|e| SceneError::Toml(e.to_string()))?;
+        scene.check_version()
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, SceneError> {
+        let scene: Scene = serde_json::from_str(contents)?;
+        scene.check_version()
+    }
+
+    pub fn to_toml(&self) -> Result<String, SceneError> {
+        toml::to_string_pretty(self).map_err(|e| SceneError::Toml(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn check_version(self) -> Result<Self, SceneError> {
+        if self.version > VERSION {
+            return Err(SceneError::UnsupportedVersion(self.version));
+        }
+        self.validate()
+    }
+
+    // Rejects views the camera cannot show, whichever format they were read from
+    pub fn validate(self) -> Result<Self, SceneError> {
+        let invalid = |message: String| Err(SceneError::Format(message));
+        let [x, y] = self.view.centre;
+        if !x.is_finite() || !y.is_finite() {
+            return invalid(format!("invalid centre {}, {}", x, y));
+        }
+        let [x, y] = self.formula.seed;
+        if !x.is_finite() || !y.is_finite() {
+            return invalid(format!("invalid Julia seed {}, {}", x, y));
+        }
+        let axis_range = self.view.axis_range;
+        if !axis_range.is_finite() || axis_range <= 0.0 {
+            return invalid(format!(
+                "invalid axis_range {}, it must be above zero",
+                axis_range
+            ));
+        }
+        let formula = self.formula;
+        if !formula.exponent.is_finite() {
+            return invalid(format!("invalid exponent {}", formula.exponent));
+        }
+        if !formula.value.is_finite() {
+            return invalid(format!("invalid value {}", formula.value));
+        }
+        let colouring = self.colouring;
+        if !colouring.offset.is_finite() {
+            return invalid(format!("invalid colour offset {}", colouring.offset));
+        }
+        if !colouring.density.is_finite() {
+            return invalid(format!("invalid colour density {}", colouring.density));
+        }
+        if self.render.iterations < 1 {
+            return invalid(format!(
+                "invalid iterations {}, at least 1 is needed",
+                self.render.iterations
+            ));
+        }
+        Ok(self)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SceneError> {
+        let contents = std::fs::read_to_string(path)?;
+        if is_json(path) {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        }
+    }
+
+    // Like load, but also reads Kalles Fraktaler .kfr and XaoS .xpf location files and the
+    // scene stored in PNGs exported by this program
+    pub fn import(path: &Path) -> Result<Imported, SceneError> {
+        if has_extension(path, "png") {
+            Ok(Imported {
+                scene: Self::from_toml(&crate::export::read_png_scene(path)?)?,
+                warnings: Vec::new(),
+            })
+        } else if has_extension(path, "kfr") {
+            crate::kfr::read(&std::fs::read_to_string(path)?)
+        } else if has_extension(path, "xpf") {
+            crate::xpf::read(&std::fs::read_to_string(path)?)
+        } else {
+            Ok(Imported {
+                scene: Self::load(path)?,
+                warnings: Vec::new(),
+            })
+        }
+    }
+
+    // Like save, but writes .kfr and .xpf files in those programs' formats. Returns what
+    // could not be represented in the chosen format.
+    pub fn export(&self, path: &Path) -> Result<Vec<String>, SceneError> {
+        let (contents, warnings) = if has_extension(path, "kfr") {
+            crate::kfr::write(self)
+        } else if has_extension(path, "xpf") {
+            crate::xpf::write(self)
+        } else {
+            self.save(path)?;
+            return Ok(Vec::new());
+        };
+        std::fs::write(path, contents)?;
+        Ok(warnings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SceneError> {
+        let contents = if is_json(path) {
+            self.to_json()?
+        } else {
+            self.to_toml()?
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene_with(view: &str, render: &str) -> String {
+        format!("version = 1\n[view]\n{}\n[render]\n{}\n", view, render)
+    }
+
+    #[test]
+    fn default_scene_round_trips() {
+        let scene = Scene::default();
+        assert_eq!(Scene::from_toml(&scene.to_toml().unwrap()).unwrap(), scene);
+        assert_eq!(Scene::from_json(&scene.to_json().unwrap()).unwrap(), scene);
+    }
+
+    #[test]
+    fn rejects_views_the_camera_cannot_show() {
+        for view in [
+            "axis_range = nan",
+            "axis_range = inf",
+            "axis_range = 0.0",
+            "axis_range = -1.0",
+            "centre = [nan, 0.0]",
+            "centre = [0.0, -inf]",
+        ] {
+            let result = Scene::from_toml(&scene_with(view, ""));
+            assert!(matches!(result, Err(SceneError::Format(_))), "{}", view);
+        }
+        let julia = "version = 1\n[formula]\njulia = true\nseed = [nan, 0.0]\n";
+        assert!(matches!(
+            Scene::from_toml(julia),
+            Err(SceneError::Format(_))
+        ));
+        for iterations in ["iterations = 0", "iterations = -5"] {
+            let result = Scene::from_toml(&scene_with("", iterations));
+            assert!(
+                matches!(result, Err(SceneError::Format(_))),
+                "{}",
+                iterations
+            );
+        }
+        let result = Scene::from_json(r#"{"version": 1, "view": {"axis_range": -2.0}}"#);
+        assert!(matches!(result, Err(SceneError::Format(_))));
+    }
+
+    fn rejects_in(table: &str, field: &str) {
+        for value in ["nan", "inf", "-inf"] {
+            let toml = format!("version = 1\n[{}]\n{} = {}\n", table, field, value);
+            let result = Scene::from_toml(&toml);
+            assert!(matches!(result, Err(SceneError::Format(_))), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn rejects_a_non_finite_exponent() {
+        rejects_in("formula", "exponent");
+    }
+
+    #[test]
+    fn rejects_a_non_finite_value() {
+        rejects_in("formula", "value");
+    }
+
+    #[test]
+    fn rejects_a_non_finite_colour_offset() {
+        rejects_in("colouring", "offset");
+    }
+
+    #[test]
+    fn rejects_a_non_finite_colour_density() {
+        rejects_in("colouring", "density");
+        let result = Scene::from_json(r#"{"version": 1, "colouring": {"density": 1e999}}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reads_the_julia_seed() {
+        let scene =
+            Scene::from_toml("version = 1\n[formula]\njulia = true\nseed = [-0.8, 0.156]\n")
+                .unwrap();
+        assert!(scene.view().julia);
+        assert_eq!(scene.view().seed, [-0.8, 0.156]);
+        // Files from before Julia mode render the Mandelbrot set
+        let scene = Scene::from_toml("version = 1\n[formula]\nexponent = 3.0\n").unwrap();
+        assert!(!scene.formula.julia);
+        assert_eq!(scene.formula.exponent, 3.0);
+    }
+
+    #[test]
+    fn julia_scene_round_trips() {
+        let mut scene = Scene::default();
+        scene.view.centre = [0.0, 0.0];
+        scene.formula.julia = true;
+        scene.formula.seed = [-0.7269, 0.1889];
+        let toml = scene.to_toml().unwrap();
+        assert!(toml.contains("julia = true"), "{}", toml);
+        assert_eq!(Scene::from_toml(&toml).unwrap(), scene);
+        assert_eq!(Scene::from_json(&scene.to_json().unwrap()).unwrap(), scene);
+        let view = scene.view();
+        assert_eq!(Scene::from_view(&view, scene.colouring), scene);
+    }
+
+    #[test]
+    fn accepts_deep_views() {
+        let toml = scene_with("axis_range = 1e-300", "iterations = 1");
+        assert_eq!(Scene::from_toml(&toml).unwrap().view.axis_range, 1e-300);
+    }
+}
@@ -18,19 +18,33 @@ struct Mouse {
     centre_y: f32,          // offset(24) align(4)  size(4)
 }; 
  
-struct ShaderUniform {      //            align(16) size(72)
+struct ShaderUniform {      //            align(16) size(96)
     resolution: vec2<f32>,  // offset(0)  align(8)  size(8)
     iterations: i32,        // offset(8)  align(4)  size(4)
     value: f32,             // offset(12) align(4)  size(4)
     mouse: Mouse,           // offset(16) align(16) size(32)
     @align(16) axis_range: f32,        // offset(48) align(4)  size(4)
     exponent: f32,          // offset(52) align(4)  size(4)
-    // -- implicit padding  // offset(56)           size(8)
+    palette: i32,           // offset(56) align(4)  size(4)
+    palette_offset: f32,    // offset(60) align(4)  size(4)
+    palette_density: f32,   // offset(64) align(4)  size(4)
+    count_unresolved: i32,  // offset(68) align(4)  size(4)
+    pixel_offset: vec2<f32>, // offset(72) align(8) size(8)
+    seed: vec2<f32>,        // offset(80) align(8)  size(8)
+    julia: i32,             // offset(88) align(4)  size(4)
 };
 
 @group(0) @binding(0)
 var<uniform> shader_info: ShaderUniform; 
 
+// Pixels that reached the iteration limit, for automatic iterations
+@group(0) @binding(1)
+var<storage, read_write> unresolved: atomic<u32>;
+
+// Output of fs_data for the window, recoloured by fs_colour
+@group(1) @binding(0)
+var data_texture: texture_2d<f32>;
+
 @vertex
 fn vs_main(
     model: VertexInput
@@ -42,25 +56,103 @@ fn vs_main(
 }
 
 // Mandelbrot Shader Code
-// Equation: z = z^2 + c
+// Equation: z = z^2 + c, starting from zero with c the pixel, or for a Julia set starting from
+// the pixel with c the seed
+
+// An orbit counts as having returned to a saved point within this fraction of a pixel. Near
+// parabolic points, such as the cusp at 0.25, orbits escape slowly by steps about as long as
+// their distance from the set, so a fixed distance would catch exterior pixels when zoomed in.
+const PERIOD_TOLERANCE: f32 = 1e-3;
+// The most the tolerance grows to when zoomed out, as a distance
+const MAX_PERIOD_TOLERANCE: f32 = 1e-6;
+
+// Inside the main cardioid or the period-2 bulb, which only holds for z^2 + c
+fn in_main_components(c: vec2<f32>) -> bool {
+    var x = c.x - 0.25;
+    var q = x * x + c.y * c.y;
+    if (q * (q + x) <= 0.25 * c.y * c.y) { return true; }
+    var bulb_x = c.x + 1.0;
+    return bulb_x * bulb_x + c.y * c.y <= 0.0625;
+}
+
+struct Sample {
+    // Smooth iteration count, or the iteration limit for interior points
+    iterations: f32,
+    // |z| when the orbit stopped
+    magnitude: f32,
+    // Estimated distance to the set, in the units of the complex plane
+    distance: f32,
+    interior: bool,
+    // Ran out of iterations. Points proven interior by the cardioid and cycle tests are not
+    // counted, a higher limit would not resolve them.
+    unresolved: bool,
+};
 
-fn mandelbrot(coord: vec2<f32>) -> f32 {
-    var z: vec2<f32> = vec2<f32>(0.0, 0.0);
+fn interior_sample(unresolved: bool) -> Sample {
+    return Sample(f32(shader_info.iterations), 0.0, 0.0, true, unresolved);
+}
+
+fn mandelbrot(coord: vec2<f32>) -> Sample {
+    var julia = shader_info.julia != 0;
+    // Same test as Formula::is_standard
+    if (!julia && shader_info.exponent == 2.0 && shader_info.value == 2.0 && in_main_components(coord)) {
+        return interior_sample(false);
+    }
+
+    var z: vec2<f32> = select(vec2<f32>(0.0, 0.0), coord, julia);
+    var c: vec2<f32> = select(coord, shader_info.seed, julia);
+    // Derivative of z along the real axis of the pixel, for the distance estimate. For z^2 + c
+    // this is the usual dz = 2 z dz + 1, and for a Julia set dz = 2 z dz from 1.
+    var dz: vec2<f32> = select(vec2<f32>(0.0, 0.0), vec2<f32>(1.0, 0.0), julia);
+    var dc: f32 = select(1.0, 0.0, julia);
     var iteration: i32 = 0;
+    // Brent's cycle detection: compare against a point saved at doubling intervals. An orbit
+    // that returns to an earlier point repeats forever, whatever the formula, so it is interior.
+    var saved: vec2<f32> = z;
+    var since_saved: i32 = 0;
+    var interval: i32 = 1;
+    var pixel_size = shader_info.axis_range / shader_info.resolution.y;
+    var tolerance = min(PERIOD_TOLERANCE * pixel_size, MAX_PERIOD_TOLERANCE);
+    var tolerance_squared = tolerance * tolerance;
     loop {
         if (length(z) >= 4.0 || iteration >= shader_info.iterations) { break; }
-        z = vec2<f32>(pow(abs(z.x), shader_info.exponent) - pow(abs(z.y), shader_info.exponent), shader_info.value * z.x * z.y) + coord;
+        var px = pow(abs(z.x), shader_info.exponent);
+        var py = pow(abs(z.y), shader_info.exponent);
+        // d/dx |x|^e = e |x|^e / x
+        var dpx = select(0.0, shader_info.exponent * px / z.x, z.x != 0.0);
+        var dpy = select(0.0, shader_info.exponent * py / z.y, z.y != 0.0);
+        dz = vec2<f32>(dpx * dz.x - dpy * dz.y + dc, shader_info.value * (dz.x * z.y + z.x * dz.y));
+        z = vec2<f32>(px - py, shader_info.value * z.x * z.y) + c;
         iteration += 1;
+
+        var difference = z - saved;
+        if (dot(difference, difference) < tolerance_squared) {
+            return interior_sample(false);
+        }
+        since_saved += 1;
+        if (since_saved == interval) {
+            saved = z;
+            since_saved = 0;
+            interval *= 2;
+        }
     }
-    if (iteration == shader_info.iterations) { 
-        return f32(shader_info.iterations); 
+    if (iteration == shader_info.iterations) {
+        return interior_sample(true);
     }
-    return f32(iteration) + 1.0 - log2(log2(length(z)));
+    var magnitude = length(z);
+    return Sample(
+        f32(iteration) + 1.0 - log2(log2(magnitude)),
+        magnitude,
+        magnitude * log(magnitude) / length(dz),
+        false,
+        false,
+    );
 }
 
 fn get_coordinate(fs_coord: vec4<f32>) -> vec2<f32> {
     var aspect_ratio = shader_info.resolution.x / shader_info.resolution.y;
-    var normalised_coords: vec2<f32> = fs_coord.xy / shader_info.resolution.xy;
+    // Tiles of a larger image are offset into it, resolution is that of the whole image
+    var normalised_coords: vec2<f32> = (fs_coord.xy + shader_info.pixel_offset) / shader_info.resolution.xy;
 
     var minx = shader_info.mouse.centre_x - shader_info.axis_range/2.0 * aspect_ratio;
     var maxx = shader_info.mouse.centre_x + shader_info.axis_range/2.0 * aspect_ratio;
@@ -73,11 +165,57 @@ fn get_coordinate(fs_coord: vec4<f32>) -> vec2<f32> {
     return normalised_coords;
 }
 
+// Cosine gradients, see https://iquilezles.org/articles/palettes/
+fn cosine_palette(t: f32, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, d: vec3<f32>) -> vec3<f32> {
+    return a + b * cos(6.28318 * (c * t + d));
+}
+
+// Indices match Palette::index
+fn palette(shade: f32) -> vec3<f32> {
+    var t = fract(shade * shader_info.palette_density + shader_info.palette_offset);
+    switch shader_info.palette {
+        case 1: { return cosine_palette(t, vec3<f32>(0.5), vec3<f32>(0.5), vec3<f32>(1.0, 0.7, 0.4), vec3<f32>(0.0, 0.15, 0.2)); }
+        case 2: { return cosine_palette(t, vec3<f32>(0.5), vec3<f32>(0.5), vec3<f32>(1.0), vec3<f32>(0.3, 0.2, 0.2)); }
+        case 3: { return cosine_palette(t, vec3<f32>(0.5), vec3<f32>(0.5), vec3<f32>(1.0), vec3<f32>(0.0, 0.33, 0.67)); }
+        default: { return vec3<f32>(t); }
+    }
+}
+
+// Kept apart from mandelbrot() so the palette can change without iterating again
+fn colour(iterations: f32, interior: bool) -> vec4<f32> {
+    if interior {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    return vec4<f32>(palette(iterations / f32(shader_info.iterations)), 1.0);
+}
+
+fn count_unresolved(sample: Sample) {
+    if sample.unresolved && shader_info.count_unresolved != 0 {
+        atomicAdd(&unresolved, 1u);
+    }
+}
+
+// Iterates and colours in one pass, for exports
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var coord: vec2<f32> = get_coordinate(in.position);
-    var iterations = mandelbrot(coord);
-    var shade = 0.0;
-    if iterations != f32(shader_info.iterations) { shade = iterations / f32(shader_info.iterations); }
-    return vec4<f32>(shade);
-}
\ No newline at end of file
+    var sample = mandelbrot(coord);
+    count_unresolved(sample);
+    return colour(sample.iterations, sample.interior);
+}
+
+// Raw per-pixel data, written to a float target for the window's colouring pass and by data
+// exports for offline recolouring
+@fragment
+fn fs_data(in: VertexOutput) -> @location(0) vec4<f32> {
+    var sample = mandelbrot(get_coordinate(in.position));
+    count_unresolved(sample);
+    return vec4<f32>(sample.iterations, sample.magnitude, sample.distance, select(0.0, 1.0, sample.interior));
+}
+
+// Colours the window from the data pass
+@fragment
+fn fs_colour(in: VertexOutput) -> @location(0) vec4<f32> {
+    var data = textureLoad(data_texture, vec2<i32>(in.position.xy), 0);
+    return colour(data.x, data.w > 0.5);
+}
@@ -0,0 +1,180 @@
+// Compact location tokens for pasting into chat, e.g. the starting view is
+// "mbx1:exSuR-F66L8AAAAAAAAAAAAAAAAAAABA9AEAAAAAAEAAAABAAAAAAAAAAIA_"
+//
+// The token is the prefix followed by the scene packed little-endian and base64url encoded:
+// centre x, centre y, axis_range (f64), iterations (i32), exponent, value (f32), palette (u8),
+// palette offset, palette density (f32). Julia sets append the seed's x and y (f64), so older
+// tokens still read as the Mandelbrot set. Coordinates keep their full f64 precision.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::scene::{Palette, Scene, SceneError};
+
+const PREFIX: &str = "mbx1:";
+const LENGTH: usize = 45;
+const JULIA_LENGTH: usize = LENGTH + 16;
+
+pub fn encode(scene: &Scene) -> String {
+    let mut bytes = Vec::with_capacity(JULIA_LENGTH);
+    bytes.extend_from_slice(&scene.view.centre[0].to_le_bytes());
+    bytes.extend_from_slice(&scene.view.centre[1].to_le_bytes());
+    bytes.extend_from_slice(&scene.view.axis_range.to_le_bytes());
+    bytes.extend_from_slice(&scene.render.iterations.to_le_bytes());
+    bytes.extend_from_slice(&scene.formula.exponent.to_le_bytes());
+    bytes.extend_from_slice(&scene.formula.value.to_le_bytes());
+    bytes.push(scene.colouring.palette.index() as u8);
+    bytes.extend_from_slice(&scene.colouring.offset.to_le_bytes());
+    bytes.extend_from_slice(&scene.colouring.density.to_le_bytes());
+    if scene.formula.julia {
+        bytes.extend_from_slice(&scene.formula.seed[0].to_le_bytes());
+        bytes.extend_from_slice(&scene.formula.seed[1].to_le_bytes());
+    }
+    format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+}
+
+// Finds the first token in `text`, so a whole chat message can be pasted
+pub fn decode(text: &str) -> Result<Scene, SceneError> {
+    let start = text
+        .find(PREFIX)
+        .ok_or_else(|| SceneError::Format("no location token found".to_string()))?
+        + PREFIX.len();
+    let token: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+    let bytes = URL_SAFE_NO_PAD
+        .decode(token)
+        .map_err(|e| SceneError::Format(format!("invalid location token: {}", e)))?;
+    if bytes.len() != LENGTH && bytes.len() != JULIA_LENGTH {
+        return Err(SceneError::Format(
+            "location token has the wrong length".to_string(),
+        ));
+    }
+
+    let mut reader = Reader { bytes: &bytes };
+    let mut scene = Scene::default();
+    scene.view.centre = [reader.f64(), reader.f64()];
+    scene.view.axis_range = reader.f64();
+    scene.render.iterations = i32::from_le_bytes(reader.take());
+    scene.formula.exponent = reader.f32();
+    scene.formula.value = reader.f32();
+    let [palette] = reader.take();
+    scene.colouring.palette = Palette::from_index(palette as i32)
+        .ok_or_else(|| SceneError::Format(format!("unknown palette {}", palette)))?;
+    scene.colouring.offset = reader.f32();
+    scene.colouring.density = reader.f32();
+    if bytes.len() == JULIA_LENGTH {
+        scene.formula.julia = true;
+        scene.formula.seed = [reader.f64(), reader.f64()];
+    }
+    scene.validate()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        head.try_into().unwrap()
+    }
+
+    fn f64(&mut self) -> f64 {
+        f64::from_le_bytes(self.take())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_TOKEN: &str = "mbx1:exSuR-F66L8AAAAAAAAAAAAAAAAAAABA9AEAAAAAAEAAAABAAAAAAAAAAIA_";
+
+    fn is_format_error(result: Result<Scene, SceneError>) -> bool {
+        matches!(result, Err(SceneError::Format(_)))
+    }
+
+    #[test]
+    fn round_trips_through_encode() {
+        let mut scene = Scene::default();
+        scene.view.centre = [-1.769383179195515, 0.0042368479187367];
+        scene.view.axis_range = 3.4e-290;
+        scene.render.iterations = 1_000_000;
+        scene.formula.exponent = 3.0;
+        scene.formula.value = -2.5;
+        scene.colouring.palette = Palette::Rainbow;
+        scene.colouring.offset = 0.125;
+        scene.colouring.density = 4.0;
+        for scene in [Scene::default(), scene] {
+            let token = encode(&scene);
+            assert_eq!(token.len(), PREFIX.len() + 60);
+            assert_eq!(decode(&token).unwrap(), scene);
+        }
+    }
+
+    #[test]
+    fn round_trips_julia_sets() {
+        let mut scene = Scene::default();
+        scene.view.centre = [0.0, 0.0];
+        scene.formula.julia = true;
+        scene.formula.seed = [-0.7269, 0.1889];
+        let token = encode(&scene);
+        assert_eq!(token.len(), PREFIX.len() + 82);
+        assert_eq!(decode(&token).unwrap(), scene);
+        // The seed is only written for Julia sets
+        scene.formula.julia = false;
+        let decoded = decode(&encode(&scene)).unwrap();
+        assert_eq!(decoded.formula, Scene::default().formula);
+    }
+
+    #[test]
+    fn decodes_the_documented_token() {
+        assert_eq!(encode(&Scene::default()), DEFAULT_TOKEN);
+        assert_eq!(decode(DEFAULT_TOKEN).unwrap(), Scene::default());
+    }
+
+    #[test]
+    fn finds_the_token_in_a_message() {
+        let message = format!("look at this: {}, isn't it nice?", DEFAULT_TOKEN);
+        assert_eq!(decode(&message).unwrap(), Scene::default());
+    }
+
+    #[test]
+    fn rejects_broken_tokens() {
+        let truncated = &DEFAULT_TOKEN[..DEFAULT_TOKEN.len() - 4];
+        assert!(is_format_error(decode(truncated)));
+        assert!(is_format_error(decode(&DEFAULT_TOKEN[..PREFIX.len()])));
+        assert!(is_format_error(decode(&format!("{}AAAA", DEFAULT_TOKEN))));
+        assert!(is_format_error(decode(
+            &DEFAULT_TOKEN.replace("mbx1:", "mbx2:")
+        )));
+        assert!(is_format_error(decode("no token here")));
+        // A single character is never valid base64
+        assert!(is_format_error(decode("mbx1:A")));
+        // The token ends at the first character outside the alphabet
+        assert!(is_format_error(decode(
+            &DEFAULT_TOKEN.replace("9AE", "9+E")
+        )));
+    }
+
+    #[test]
+    fn rejects_unknown_palettes_and_invalid_views() {
+        let mut scene = Scene::default();
+        scene.view.axis_range = -1.0;
+        assert!(is_format_error(decode(&encode(&scene))));
+
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(&DEFAULT_TOKEN[PREFIX.len()..])
+            .unwrap();
+        // centre, axis_range, iterations, exponent and value come first
+        bytes[36] = 200;
+        let token = format!("{}{}", PREFIX, URL_SAFE_NO_PAD.encode(bytes));
+        assert!(is_format_error(decode(&token)));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::readback::Readback;
+
+// Measures the fractal pass on the GPU with timestamp queries, when the adapter supports them
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback: Readback,
+    // Nanoseconds per timestamp tick
+    period: f64,
+}
+
+const QUERY_BYTES: u64 = 2 * wgpu::QUERY_SIZE as u64;
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Frame Timer"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Frame Timer Resolve"),
+            size: QUERY_BYTES,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback: Readback::new(device, QUERY_BYTES, "Frame Timer Readback"),
+            period: queue.get_timestamp_period() as f64,
+        })
+    }
+
+    // Whether this frame should be timed, i.e. the previous timing has been read
+    pub fn ready(&self) -> bool {
+        self.readback.ready()
+    }
+
+    pub fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        self.readback.copy_from(encoder, &self.resolve_buffer);
+    }
+
+    // Call after the encoder passed to end has been submitted
+    pub fn request(&mut self) {
+        self.readback.request();
+    }
+
+    // Returns the last measured duration in seconds once it has been read back
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<f64> {
+        let data = self.readback.poll(device)?;
+        let timestamps: [u64; 2] = bytemuck::pod_read_unaligned(&data[..QUERY_BYTES as usize]);
+        let ticks = timestamps[1].wrapping_sub(timestamps[0]);
+        Some(ticks as f64 * self.period * 1e-9)
+    }
+}
@@ -1,7 +1,180 @@
+use std::path::PathBuf;
+
+use crate::{
+    animation::{Destination, Frame, Interpolation, PaletteCycle, Timeline, ZoomAnimation},
+    bookmarks::{self, Bookmarks},
+    camera::Camera,
+    export::ImageFormat,
+    history::History,
+    iterations::AutoIterations,
+    misiurewicz::MisiurewiczPoint,
+    nucleus::Nucleus,
+    orbit::Orbit,
+    rays::{Angle, Ray},
+    scene::{Colouring, Palette},
+    video::{self, VideoSettings},
+    view::View,
+};
+
+// Requests from the interface that State carries out on the next update
+pub enum Command {
+    GoTo(View),
+    HistoryJump(usize),
+    SaveBookmark(String),
+    DeleteBookmark(usize),
+    OpenScene(PathBuf),
+    SaveScene(PathBuf),
+    // Searches from the centre of the view
+    FindNucleus,
+    FindMisiurewicz,
+    TraceRay(Angle),
+    ExportImage {
+        path: PathBuf,
+        format: ImageFormat,
+        width: u32,
+        height: u32,
+    },
+    // Cancels image exports and recordings alike
+    CancelExport,
+    // Capture the current view as an end of the zoom animation
+    SetZoomStart,
+    SetZoomEnd,
+    // Writes the frames of a zoom or the timeline as numbered PNGs or a video
+    Record {
+        frames: Vec<Frame>,
+        destination: Destination,
+        width: u32,
+        height: u32,
+    },
+    // One full turn of the palette cycle from the current view, at a frame rate
+    RecordPaletteCycle {
+        frame_rate: u32,
+        destination: Destination,
+        width: u32,
+        height: u32,
+    },
+    // Keyframes the current view at a time on the timeline, or into an existing keyframe
+    AddKeyframe(f64),
+    UpdateKeyframe(usize),
+    SeekTimeline(f64),
+    CopyLocation,
+    PasteLocation,
+}
+
+// Live figures shown in the overlay, filled in by State every frame
+#[derive(Default)]
+pub struct Info {
+    pub cursor: [f64; 2],
+    pub centre: [f64; 2],
+    pub axis_range: f64,
+    pub zoom: f64,
+    pub iterations: i32,
+    // Seconds, the GPU time is only known when the adapter supports timestamp queries
+    pub frame_time: f64,
+    pub gpu_time: Option<f64>,
+    pub adapter: String,
+}
+
+impl Info {
+    pub fn text(&self) -> String {
+        // Enough decimals to tell neighbouring pixels apart, up to what an f64 holds
+        let decimals = (3.0 - self.axis_range.log10()).clamp(2.0, 17.0) as usize;
+        let mut lines = vec![
+            format!(
+                "Cursor  {:+.*} {:+.*}i",
+                decimals, self.cursor[0], decimals, self.cursor[1]
+            ),
+            format!(
+                "Centre  {:+.*} {:+.*}i",
+                decimals, self.centre[0], decimals, self.centre[1]
+            ),
+            format!("Zoom    {:.3e}", self.zoom),
+            format!("Iters   {}", self.iterations),
+            format!(
+                "Frame   {:.2} ms ({:.0} fps)",
+                self.frame_time * 1000.0,
+                1.0 / self.frame_time.max(1e-6)
+            ),
+        ];
+        if let Some(gpu_time) = self.gpu_time {
+            lines.push(format!("GPU     {:.2} ms", gpu_time * 1000.0));
+        }
+        lines.push(format!("Adapter {}", self.adapter));
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SceneDialog {
+    Open,
+    Save,
+}
+
 pub struct Interface {
     pub iterations: i32,
     pub value: f32,
-    pub exponent:f32,
+    pub exponent: f32,
+    pub julia: bool,
+    pub seed: [f64; 2],
+    pub colouring: Colouring,
+    pub palette_cycle: PaletteCycle,
+    pub visible: bool,
+    pub commands: Vec<Command>,
+    // Result of the last file operation, shown at the bottom of the window
+    pub status: String,
+    bookmark_name: String,
+    scene_dialog: Option<SceneDialog>,
+    scene_path: String,
+    // Box zoom selection in points, drawn over the fractal while dragging
+    pub selection: Option<egui::Rect>,
+    pub info: Info,
+    pub show_overlay: bool,
+    pub auto_iterations: AutoIterations,
+    // Period searched for by the nucleus finder, 0 detects it from the orbit
+    pub nucleus_period: usize,
+    pub nucleus: Option<Nucleus>,
+    pub misiurewicz_preperiod: usize,
+    pub misiurewicz_period: usize,
+    // Search every preperiod and period up to the chosen ones instead of that pair alone
+    pub misiurewicz_up_to: bool,
+    pub misiurewicz: Vec<MisiurewiczPoint>,
+    export_dialog: bool,
+    export_path: String,
+    export_format: ImageFormat,
+    export_size: [u32; 2],
+    // Share of the running image export done, None when no export is running
+    pub export_progress: Option<f32>,
+    pub zoom_start: Option<Frame>,
+    pub zoom_end: Option<Frame>,
+    zoom_duration: f64,
+    // Output of zoom and timeline recordings
+    record_frame_rate: u32,
+    record_directory: String,
+    record_size: [u32; 2],
+    // Encode a video with ffmpeg rather than writing PNGs
+    record_video: bool,
+    record_video_path: String,
+    record_codec: String,
+    record_crf: u32,
+    // Frames written, frames in total and the share of the current frame done while recording
+    pub recording_progress: Option<(usize, usize, f32)>,
+    pub timeline: Timeline,
+    show_timeline: bool,
+    // Playhead in seconds, moved on by State while playing
+    pub timeline_time: f64,
+    pub timeline_playing: bool,
+    pub timeline_looping: bool,
+    ray_angle: String,
+    pub rays: Vec<Ray>,
+    // Traced rays in screen points, refreshed every frame
+    pub ray_lines: Vec<Vec<egui::Pos2>>,
+    // Binary expansions and angles of rays near the last Alt-clicked point
+    pub angle_estimates: Vec<(String, Angle)>,
+    // An estimate is running, its angles replace angle_estimates when it finishes
+    pub estimating_angles: bool,
+    // Last traced orbit, and its points in screen points while it is being traced
+    pub orbit: Option<Orbit>,
+    pub orbit_points: Vec<egui::Pos2>,
 }
 
 impl Interface {
@@ -10,25 +183,930 @@ impl Interface {
             iterations: 500,
             value: 2.0,
             exponent: 2.0,
+            julia: false,
+            seed: [0.0, 0.0],
+            colouring: Colouring::default(),
+            palette_cycle: PaletteCycle::new(),
+            visible: true,
+            commands: Vec::new(),
+            status: String::new(),
+            bookmark_name: String::new(),
+            scene_dialog: None,
+            scene_path: String::from("scene.toml"),
+            selection: None,
+            info: Info::default(),
+            show_overlay: false,
+            auto_iterations: AutoIterations::new(),
+            nucleus_period: 0,
+            nucleus: None,
+            misiurewicz_preperiod: 4,
+            misiurewicz_period: 1,
+            misiurewicz_up_to: true,
+            misiurewicz: Vec::new(),
+            export_dialog: false,
+            export_path: String::from("mandelbrot.png"),
+            export_format: ImageFormat::Png8,
+            export_size: [3840, 2160],
+            export_progress: None,
+            zoom_start: None,
+            zoom_end: None,
+            zoom_duration: 10.0,
+            record_frame_rate: 30,
+            record_directory: String::from("frames"),
+            record_size: [1920, 1080],
+            record_video: false,
+            record_video_path: String::from("zoom.mp4"),
+            record_codec: String::from(video::CODECS[0]),
+            record_crf: 18,
+            recording_progress: None,
+            timeline: Timeline::default(),
+            show_timeline: false,
+            timeline_time: 0.0,
+            timeline_playing: false,
+            timeline_looping: false,
+            ray_angle: String::from("1/7"),
+            rays: Vec::new(),
+            ray_lines: Vec::new(),
+            angle_estimates: Vec::new(),
+            estimating_angles: false,
+            orbit: None,
+            orbit_points: Vec::new(),
         }
     }
 
-    pub fn ui(&mut self, ctx: &egui::Context) {
+    pub fn ui(&mut self, ctx: &egui::Context, history: &History, bookmarks: &Bookmarks) {
         egui::CentralPanel::default()
             .frame(egui::Frame::none())
-            .show(ctx, |_| {
+            .show(ctx, |ui| {
+                if let Some(selection) = self.selection {
+                    ui.painter().rect(
+                        selection,
+                        0.0,
+                        egui::Color32::from_white_alpha(24),
+                        egui::Stroke::new(1.0, egui::Color32::WHITE),
+                    );
+                }
+                let ray_colour = egui::Color32::from_rgb(120, 220, 255);
+                for (ray, line) in self.rays.iter().zip(&self.ray_lines) {
+                    ui.painter().add(egui::Shape::line(
+                        line.clone(),
+                        egui::Stroke::new(1.5, ray_colour),
+                    ));
+                    if let Some(&end) = line.last() {
+                        ui.painter().text(
+                            end,
+                            egui::Align2::LEFT_BOTTOM,
+                            ray.angle.to_string(),
+                            egui::FontId::monospace(12.0),
+                            ray_colour,
+                        );
+                    }
+                }
+                if !self.orbit_points.is_empty() {
+                    let stroke = egui::Stroke::new(1.0, egui::Color32::from_rgb(255, 200, 0));
+                    ui.painter()
+                        .add(egui::Shape::line(self.orbit_points.clone(), stroke));
+                    for &point in &self.orbit_points {
+                        ui.painter().circle_filled(point, 2.0, stroke.color);
+                    }
+                }
+
+                let mut visible = self.visible;
                 egui::Window::new("Fractal Playground")
                     .default_open(true)
+                    .open(&mut visible)
                     .show(ctx, |ui: &mut egui::Ui| {
                         ui.collapsing("Parameters", |ui| {
                             ui.label("Iterations");
-                            ui.add(egui::Slider::new(&mut self.iterations, 0..=3000).text("Iterations"));
+                            // Moving the slider overrides the automatic limit
+                            let slider = ui.add(
+                                egui::Slider::new(&mut self.iterations, 1..=3000)
+                                    .text("Iterations"),
+                            );
+                            if slider.changed() {
+                                self.auto_iterations.enabled = false;
+                            }
+                            self.auto_iterations_ui(ui);
                             ui.label("Value");
                             ui.add(egui::Slider::new(&mut self.value, -10.0..=10.0).text("Value"));
                             ui.label("Exponent");
-                            ui.add(egui::Slider::new(&mut self.exponent,0.0..=10.0).text("Exponent"));
+                            ui.add(
+                                egui::Slider::new(&mut self.exponent, 0.0..=10.0).text("Exponent"),
+                            );
+                            self.julia_ui(ui);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut self.show_overlay, "Show overlay");
+                            ui.checkbox(&mut self.show_timeline, "Show timeline");
                         });
+                        ui.collapsing("Colouring", |ui| {
+                            self.colouring_ui(ui);
+                        });
+                        ui.collapsing("Scene", |ui| {
+                            ui.horizontal(|ui| {
+                                if ui.button("Open...").clicked() {
+                                    self.scene_dialog = Some(SceneDialog::Open);
+                                }
+                                if ui.button("Save...").clicked() {
+                                    self.scene_dialog = Some(SceneDialog::Save);
+                                }
+                                if ui.button("Export image...").clicked() {
+                                    self.export_dialog = true;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.button("Copy location").clicked() {
+                                    self.commands.push(Command::CopyLocation);
+                                }
+                                if ui.button("Paste location").clicked() {
+                                    self.commands.push(Command::PasteLocation);
+                                }
+                            });
+                        });
+                        ui.collapsing("Zoom animation", |ui| {
+                            self.zoom_animation_ui(ui);
+                        });
+                        ui.collapsing("History", |ui| {
+                            self.history_ui(ui, history);
+                        });
+                        ui.collapsing("Bookmarks", |ui| {
+                            self.bookmarks_ui(ui, bookmarks);
+                        });
+                        ui.collapsing("Nucleus", |ui| {
+                            self.nucleus_ui(ui);
+                        });
+                        ui.collapsing("Misiurewicz points", |ui| {
+                            self.misiurewicz_ui(ui);
+                        });
+                        ui.collapsing("External rays", |ui| {
+                            self.rays_ui(ui);
+                        });
+                        if !self.status.is_empty() {
+                            ui.separator();
+                            ui.label(&self.status);
+                        }
+                    });
+                self.visible = visible;
+
+                self.scene_dialog_ui(ctx);
+                self.export_dialog_ui(ctx);
+                if self.show_overlay {
+                    self.overlay_ui(ctx);
+                }
+                self.orbit_ui(ctx);
+                self.timeline_ui(ctx);
+            });
+    }
+
+    fn orbit_ui(&mut self, ctx: &egui::Context) {
+        let Some(orbit) = &self.orbit else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Orbit").open(&mut open).show(ctx, |ui| {
+            let start = orbit.points[0];
+            if start != [0.0, 0.0] {
+                ui.label(format!("z0 = {:+.17} {:+.17}i", start[0], start[1]));
+            }
+            ui.label(format!("c = {:+.17} {:+.17}i", orbit.c[0], orbit.c[1]));
+            ui.label(match orbit.escaped {
+                Some(iteration) => format!("Escaped at iteration {}", iteration),
+                None => format!("Bounded for {} iterations", orbit.points.len() - 1),
+            });
+            ui.label(match (orbit.escaped, orbit.period) {
+                (Some(_), _) => "No period, the orbit escaped".to_string(),
+                (None, Some(period)) => format!("Period {}", period),
+                (None, None) => "No period detected".to_string(),
+            });
+            let magnitudes: egui::plot::PlotPoints = orbit
+                .points
+                .iter()
+                .enumerate()
+                .map(|(n, z)| [n as f64, z[0].hypot(z[1])])
+                .collect();
+            egui::plot::Plot::new("orbit_magnitude")
+                .height(160.0)
+                .allow_scroll(false)
+                .show(ui, |plot_ui| {
+                    plot_ui.line(egui::plot::Line::new(magnitudes).name("|z_n|"))
+                });
+        });
+        if !open {
+            self.orbit = None;
+        }
+    }
+
+    fn overlay_ui(&self, ctx: &egui::Context) {
+        egui::Area::new("overlay")
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    let text = self.info.text();
+                    ui.label(egui::RichText::new(&text).monospace());
+                    if ui.button("Copy").clicked() {
+                        ui.output_mut(|output| output.copied_text = text);
+                    }
+                });
+            });
+    }
+
+    fn auto_iterations_ui(&mut self, ui: &mut egui::Ui) {
+        let auto = &mut self.auto_iterations;
+        ui.checkbox(&mut auto.enabled, "Automatic iterations");
+        ui.add_enabled_ui(auto.enabled, |ui| {
+            ui.add(egui::Slider::new(&mut auto.base, 10.0..=2000.0).text("Base"));
+            ui.add(egui::Slider::new(&mut auto.per_decade, 0.0..=2000.0).text("Per decade"));
+            ui.add(egui::Slider::new(&mut auto.power, 0.5..=3.0).text("Power"));
+            ui.checkbox(&mut auto.adaptive, "Raise while pixels are unresolved");
+            ui.add_enabled(
+                auto.adaptive,
+                egui::Slider::new(&mut auto.unresolved_limit, 0.0..=1.0).text("Unresolved limit"),
+            );
+        });
+    }
+
+    fn colouring_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_label("Palette")
+            .selected_text(self.colouring.palette.name())
+            .show_ui(ui, |ui| {
+                for palette in Palette::ALL {
+                    ui.selectable_value(&mut self.colouring.palette, palette, palette.name());
+                }
+            });
+        ui.add(egui::Slider::new(&mut self.colouring.offset, 0.0..=1.0).text("Offset"));
+        ui.add(
+            egui::Slider::new(&mut self.colouring.density, 0.1..=50.0)
+                .logarithmic(true)
+                .text("Density"),
+        );
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.palette_cycle.enabled, "Cycle palette");
+            ui.checkbox(&mut self.palette_cycle.reverse, "Reverse");
+        });
+        ui.add(
+            egui::Slider::new(&mut self.palette_cycle.speed, 0.01..=5.0)
+                .logarithmic(true)
+                .text("Turns per second"),
+        );
+        ui.collapsing("Record cycle", |ui| {
+            ui.label("One full turn from the current view, which loops seamlessly");
+            let frame_rate = self.record_frame_rate;
+            let frame_count = self.palette_cycle.frame_count(frame_rate);
+            self.record_ui(ui, frame_count, |destination, [width, height]| {
+                Command::RecordPaletteCycle {
+                    frame_rate,
+                    destination,
+                    width,
+                    height,
+                }
+            });
+        });
+    }
+
+    // The file extension picks the format: .json for JSON, .kfr for Kalles Fraktaler,
+    // .xpf for XaoS and TOML for anything else
+    fn scene_dialog_ui(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = self.scene_dialog else {
+            return;
+        };
+        let (title, action) = match dialog {
+            SceneDialog::Open => ("Open scene", "Open"),
+            SceneDialog::Save => ("Save scene", "Save"),
+        };
+
+        let mut open = true;
+        let mut done = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path");
+                    ui.text_edit_singleline(&mut self.scene_path);
+                });
+                let path = self.scene_path.trim();
+                if ui
+                    .add_enabled(!path.is_empty(), egui::Button::new(action))
+                    .clicked()
+                {
+                    let path = PathBuf::from(path);
+                    self.commands.push(match dialog {
+                        SceneDialog::Open => Command::OpenScene(path),
+                        SceneDialog::Save => Command::SaveScene(path),
+                    });
+                    done = true;
+                }
+            });
+
+        if !open || done {
+            self.scene_dialog = None;
+        }
+    }
+
+    fn export_dialog_ui(&mut self, ctx: &egui::Context) {
+        // Stays open while exporting, it holds the progress and the cancel button
+        let mut open = self.export_dialog || self.export_progress.is_some();
+        if !open {
+            return;
+        }
+        egui::Window::new("Export image")
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path");
+                    ui.text_edit_singleline(&mut self.export_path);
+                });
+                let format = self.export_format;
+                egui::ComboBox::from_label("Format")
+                    .selected_text(format.name())
+                    .show_ui(ui, |ui| {
+                        for option in ImageFormat::ALL {
+                            ui.selectable_value(&mut self.export_format, option, option.name());
+                        }
+                    });
+                if self.export_format != format {
+                    let path = PathBuf::from(self.export_path.trim())
+                        .with_extension(self.export_format.extension());
+                    self.export_path = path.display().to_string();
+                }
+                if self.export_format == ImageFormat::ExrData {
+                    ui.label(
+                        "Channels: smooth iteration count, final |z|, distance estimate and \
+                         interior flag. The whole image is held in memory until it is written.",
+                    );
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Size");
+                    ui.add(egui::DragValue::new(&mut self.export_size[0]).clamp_range(1..=65535));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut self.export_size[1]).clamp_range(1..=65535));
+                });
+                ui.horizontal(|ui| {
+                    for (label, size) in [
+                        ("1080p", [1920, 1080]),
+                        ("4K", [3840, 2160]),
+                        ("8K", [7680, 4320]),
+                        ("Poster", [16000, 16000]),
+                    ] {
+                        if ui.button(label).clicked() {
+                            self.export_size = size;
+                        }
+                    }
+                });
+                ui.label("The view keeps its height, the width follows the aspect ratio");
+
+                match self.export_progress {
+                    Some(progress) => {
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                        if ui.button("Cancel").clicked() {
+                            self.commands.push(Command::CancelExport);
+                        }
+                    }
+                    None => {
+                        let path = self.export_path.trim();
+                        if ui
+                            .add_enabled(!path.is_empty(), egui::Button::new("Export"))
+                            .clicked()
+                        {
+                            self.commands.push(Command::ExportImage {
+                                path: PathBuf::from(path),
+                                format: self.export_format,
+                                width: self.export_size[0],
+                                height: self.export_size[1],
+                            });
+                        }
+                    }
+                }
+            });
+        self.export_dialog = open;
+    }
+
+    fn zoom_animation_ui(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("zoom_ends").show(ui, |ui| {
+            for (label, frame, command) in [
+                ("Start", self.zoom_start, Command::SetZoomStart),
+                ("End", self.zoom_end, Command::SetZoomEnd),
+            ] {
+                ui.label(label);
+                if ui.button("Set to view").clicked() {
+                    self.commands.push(command);
+                }
+                match frame {
+                    Some(frame) => {
+                        if ui.button("Go to").clicked() {
+                            self.commands.push(Command::GoTo(frame.view));
+                        }
+                        ui.label(format!("×{:.3e}", frame.view.zoom()));
+                    }
+                    None => {
+                        ui.label("not set");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Duration");
+            ui.add(
+                egui::DragValue::new(&mut self.zoom_duration)
+                    .clamp_range(0.1..=3600.0)
+                    .suffix(" s"),
+            );
+        });
+        ui.label("Iterations and palette offset move from the start to the end");
+
+        let (Some(start), Some(end)) = (self.zoom_start, self.zoom_end) else {
+            ui.label("Set the start and end views to record");
+            return;
+        };
+        let animation = ZoomAnimation {
+            start,
+            end,
+            duration: self.zoom_duration,
+            frame_rate: self.record_frame_rate,
+        };
+        self.record_ui(
+            ui,
+            animation.frame_count(),
+            |destination, [width, height]| Command::Record {
+                frames: animation.frames(),
+                destination,
+                width,
+                height,
+            },
+        );
+    }
+
+    // Output settings shared by zoom and timeline recordings, and the recording's progress
+    fn record_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        frame_count: usize,
+        record: impl FnOnce(Destination, [u32; 2]) -> Command,
+    ) {
+        ui.horizontal(|ui| {
+            ui.label("Frame rate");
+            ui.add(
+                egui::DragValue::new(&mut self.record_frame_rate)
+                    .clamp_range(1..=240)
+                    .suffix(" fps"),
+            );
+            ui.label("Size");
+            ui.add(egui::DragValue::new(&mut self.record_size[0]).clamp_range(1..=65535));
+            ui.label("×");
+            ui.add(egui::DragValue::new(&mut self.record_size[1]).clamp_range(1..=65535));
+        });
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.record_video, false, "PNG frames");
+            ui.radio_value(&mut self.record_video, true, "Video with ffmpeg");
+        });
+        if self.record_video {
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut self.record_video_path);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Codec");
+                ui.add(egui::TextEdit::singleline(&mut self.record_codec).desired_width(96.0));
+                egui::ComboBox::from_id_source("codec")
+                    .selected_text("Presets")
+                    .show_ui(ui, |ui| {
+                        for codec in video::CODECS {
+                            ui.selectable_value(&mut self.record_codec, codec.to_string(), codec);
+                        }
                     });
+                ui.label("CRF");
+                let max_crf = video::max_crf(self.record_codec.trim()).unwrap_or(63);
+                self.record_crf = self.record_crf.min(max_crf);
+                ui.add(egui::DragValue::new(&mut self.record_crf).clamp_range(0..=max_crf));
             });
+            ui.label("Falls back to PNG frames when ffmpeg is not installed");
+        } else {
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                ui.text_edit_singleline(&mut self.record_directory);
+            });
+        }
+
+        if let Some((done, count, frame_progress)) = self.recording_progress {
+            let progress = (done as f32 + frame_progress) / count as f32;
+            ui.add(egui::ProgressBar::new(progress).text(format!(
+                "Frame {} of {}",
+                (done + 1).min(count),
+                count
+            )));
+            if ui.button("Cancel").clicked() {
+                self.commands.push(Command::CancelExport);
+            }
+            return;
+        }
+        let destination = if self.record_video {
+            Destination::Video {
+                path: PathBuf::from(self.record_video_path.trim()),
+                settings: VideoSettings {
+                    codec: self.record_codec.trim().to_string(),
+                    crf: self.record_crf,
+                    frame_rate: self.record_frame_rate,
+                },
+            }
+        } else {
+            Destination::Images(PathBuf::from(self.record_directory.trim()))
+        };
+        let label = format!("Record {} frames", frame_count);
+        let ready = !destination.path().as_os_str().is_empty() && frame_count > 0;
+        if ui.add_enabled(ready, egui::Button::new(label)).clicked() {
+            self.commands.push(record(destination, self.record_size));
+        }
+    }
+
+    fn timeline_ui(&mut self, ctx: &egui::Context) {
+        if !self.show_timeline {
+            return;
+        }
+        let mut open = true;
+        egui::Window::new("Timeline")
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                let duration = self.timeline.duration();
+                let mut seek = None;
+                ui.horizontal(|ui| {
+                    let label = if self.timeline_playing {
+                        "Pause"
+                    } else {
+                        "Play"
+                    };
+                    let playable = !self.timeline.keyframes.is_empty();
+                    if ui.add_enabled(playable, egui::Button::new(label)).clicked() {
+                        if !self.timeline_playing && self.timeline_time >= duration {
+                            self.timeline_time = 0.0;
+                        }
+                        self.timeline_playing = !self.timeline_playing;
+                        self.auto_iterations.enabled = false;
+                    }
+                    ui.checkbox(&mut self.timeline_looping, "Loop");
+                    let time = ui.add(
+                        egui::DragValue::new(&mut self.timeline_time)
+                            .clamp_range(0.0..=3600.0)
+                            .speed(0.05)
+                            .suffix(" s"),
+                    );
+                    if time.changed() {
+                        seek = Some(self.timeline_time);
+                    }
+                    if ui.button("Add keyframe").clicked() {
+                        self.commands.push(Command::AddKeyframe(self.timeline_time));
+                    }
+                });
+                if let Some(time) = self.timeline_track(ui) {
+                    seek = Some(time);
+                }
+
+                let mut remove = None;
+                let mut reorder = false;
+                egui::Grid::new("keyframes").striped(true).show(ui, |ui| {
+                    for (index, keyframe) in self.timeline.keyframes.iter_mut().enumerate() {
+                        let time = ui.add(
+                            egui::DragValue::new(&mut keyframe.time)
+                                .clamp_range(0.0..=3600.0)
+                                .speed(0.05)
+                                .suffix(" s"),
+                        );
+                        reorder |= time.changed();
+                        egui::ComboBox::from_id_source(("interpolation", index))
+                            .selected_text(keyframe.interpolation.name())
+                            .show_ui(ui, |ui| {
+                                for option in Interpolation::ALL {
+                                    ui.selectable_value(
+                                        &mut keyframe.interpolation,
+                                        option,
+                                        option.name(),
+                                    );
+                                }
+                            });
+                        ui.label(format!("×{:.3e}", keyframe.frame.view.zoom()));
+                        if ui.button("Go to").clicked() {
+                            seek = Some(keyframe.time);
+                        }
+                        if ui.button("Update").clicked() {
+                            self.commands.push(Command::UpdateKeyframe(index));
+                        }
+                        if ui.button("Delete").clicked() {
+                            remove = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(index) = remove {
+                    self.timeline.keyframes.remove(index);
+                }
+                if reorder {
+                    self.timeline.sort();
+                }
+                if let Some(time) = seek {
+                    self.timeline_time = time;
+                    self.timeline_playing = false;
+                    self.auto_iterations.enabled = false;
+                    self.commands.push(Command::SeekTimeline(time));
+                }
+
+                ui.separator();
+                ui.label("Each keyframe's interpolation is used up to the next keyframe");
+                let frame_rate = self.record_frame_rate;
+                let timeline = self.timeline.clone();
+                self.record_ui(
+                    ui,
+                    timeline.frame_count(frame_rate),
+                    |destination, [width, height]| Command::Record {
+                        frames: timeline.frames(frame_rate),
+                        destination,
+                        width,
+                        height,
+                    },
+                );
+            });
+        self.show_timeline = open;
+    }
+
+    // Draws the keyframes and playhead on a strip, returning the time clicked or dragged to
+    fn timeline_track(&self, ui: &mut egui::Ui) -> Option<f64> {
+        let span = self.timeline.duration().max(self.timeline_time).max(1.0);
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 28.0),
+            egui::Sense::click_and_drag(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let x = |time: f64| rect.left() + (time / span) as f32 * rect.width();
+
+        let colour = egui::Color32::from_rgb(255, 200, 0);
+        for keyframe in &self.timeline.keyframes {
+            let centre = egui::pos2(x(keyframe.time), rect.center().y);
+            let size = 6.0;
+            painter.add(egui::Shape::convex_polygon(
+                vec![
+                    centre + egui::vec2(0.0, -size),
+                    centre + egui::vec2(size, 0.0),
+                    centre + egui::vec2(0.0, size),
+                    centre + egui::vec2(-size, 0.0),
+                ],
+                colour,
+                egui::Stroke::NONE,
+            ));
+        }
+        let playhead = x(self.timeline_time);
+        painter.line_segment(
+            [
+                egui::pos2(playhead, rect.top()),
+                egui::pos2(playhead, rect.bottom()),
+            ],
+            egui::Stroke::new(2.0, egui::Color32::RED),
+        );
+
+        if !(response.clicked() || response.dragged()) {
+            return None;
+        }
+        let position = response.interact_pointer_pos()?;
+        let share = ((position.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+        Some(share as f64 * span)
+    }
+
+    fn history_ui(&mut self, ui: &mut egui::Ui, history: &History) {
+        egui::ScrollArea::vertical()
+            .max_height(240.0)
+            .show(ui, |ui| {
+                // Newest first
+                for (index, entry) in history.entries().iter().enumerate().rev() {
+                    ui.horizontal(|ui| {
+                        let thumbnail = egui::ImageButton::new(
+                            entry.thumbnail.id(),
+                            entry.thumbnail.size_vec2(),
+                        )
+                        .selected(index == history.current());
+                        if ui.add(thumbnail).clicked() {
+                            self.commands.push(Command::HistoryJump(index));
+                        }
+                        ui.label(format!(
+                            "Zoom {:.3e}\n{} iterations",
+                            entry.view.zoom(),
+                            entry.view.iterations
+                        ));
+                    });
+                }
+            });
+    }
+
+    fn nucleus_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Period");
+            ui.add(egui::DragValue::new(&mut self.nucleus_period).clamp_range(0..=100_000));
+            if self.nucleus_period == 0 {
+                ui.label("(detected)");
+            }
+        });
+        if ui.button("Find near centre").clicked() {
+            self.commands.push(Command::FindNucleus);
+        }
+        ui.label("or press N over the fractal to search from the cursor");
+
+        let Some(nucleus) = &self.nucleus else {
+            return;
+        };
+        ui.separator();
+        ui.label(format!("Nucleus {:+}", nucleus.centre[0]));
+        ui.label(format!("        {:+}i", nucleus.centre[1]));
+        ui.label(format!("Period {}", nucleus.period));
+        ui.label(format!("Size {:.3e}", nucleus.size));
+        ui.label(format!("Orientation {:.1}°", nucleus.orientation));
+        if !nucleus.can_view() {
+            ui.label("Too small for the view, which is limited to f64");
+        } else if ui.button("Zoom to minibrot").clicked() {
+            let (centre, axis_range) = nucleus.view();
+            self.commands.push(Command::GoTo(View {
+                centre,
+                axis_range,
+                iterations: self.iterations,
+                value: self.value,
+                exponent: self.exponent,
+                julia: self.julia,
+                seed: self.seed,
+            }));
+        }
+    }
+
+    fn misiurewicz_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Preperiod");
+            ui.add(egui::DragValue::new(&mut self.misiurewicz_preperiod).clamp_range(2..=32));
+            ui.label("Period");
+            ui.add(egui::DragValue::new(&mut self.misiurewicz_period).clamp_range(1..=16));
+        });
+        ui.checkbox(&mut self.misiurewicz_up_to, "Search all up to these");
+        if ui.button("Search near centre").clicked() {
+            self.commands.push(Command::FindMisiurewicz);
+        }
+        ui.label("or press M over the fractal to search around the cursor");
+
+        if self.misiurewicz.is_empty() {
+            return;
+        }
+        ui.separator();
+        let mut selected = None;
+        egui::ScrollArea::vertical()
+            .id_source("misiurewicz")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for point in &self.misiurewicz {
+                    ui.horizontal(|ui| {
+                        ui.monospace(format!(
+                            "M({},{}) {:+.17} {:+.17}i",
+                            point.preperiod, point.period, point.c[0], point.c[1]
+                        ));
+                        if ui.button("Centre").clicked() {
+                            selected = Some(point.c);
+                        }
+                    });
+                }
+            });
+        if let Some(centre) = selected {
+            self.commands.push(Command::GoTo(View {
+                centre,
+                axis_range: self.info.axis_range,
+                iterations: self.iterations,
+                value: self.value,
+                exponent: self.exponent,
+                julia: self.julia,
+                seed: self.seed,
+            }));
+        }
+    }
+
+    fn julia_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.julia, "Julia set");
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            let speed = self.info.axis_range * 1e-3;
+            ui.add(egui::DragValue::new(&mut self.seed[0]).speed(speed));
+            ui.add(
+                egui::DragValue::new(&mut self.seed[1])
+                    .speed(speed)
+                    .suffix("i"),
+            );
+        });
+        // The Julia set of a point looks like the Mandelbrot set around it, and is centred on 0
+        if ui.button("Julia set of the centre").clicked() {
+            self.commands.push(Command::GoTo(View {
+                centre: [0.0, 0.0],
+                axis_range: Camera::DEFAULT_RANGE,
+                iterations: self.iterations,
+                value: self.value,
+                exponent: self.exponent,
+                julia: true,
+                seed: self.info.centre,
+            }));
+        }
+    }
+
+    fn rays_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Angle");
+            ui.text_edit_singleline(&mut self.ray_angle)
+                .on_hover_text("A fraction like 1/7, or binary like 0.(001) or 0.01(10)");
+            if ui.button("Trace").clicked() {
+                match Angle::parse(&self.ray_angle) {
+                    Ok(angle) => self.commands.push(Command::TraceRay(angle)),
+                    Err(e) => self.status = format!("Invalid angle: {}", e),
+                }
+            }
+        });
+
+        let mut removed = None;
+        for (index, ray) in self.rays.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{} = {}", ray.angle, ray.angle.binary()));
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.rays.remove(index);
+        }
+        if !self.rays.is_empty() && ui.button("Clear rays").clicked() {
+            self.rays.clear();
+        }
+
+        ui.separator();
+        ui.label("Alt-click the fractal to estimate the angles of the rays landing nearby");
+        if self.estimating_angles {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Estimating angles...");
+            });
+        }
+        let mut traced = None;
+        for (digits, angle) in &self.angle_estimates {
+            ui.horizontal(|ui| {
+                ui.monospace(digits);
+                if ui.button("Trace").clicked() {
+                    traced = Some(*angle);
+                }
+            });
+            ui.label(format!("≈ {:.12} turns", angle.turns()));
+        }
+        if let Some(angle) = traced {
+            self.commands.push(Command::TraceRay(angle));
+        }
     }
-}
\ No newline at end of file
+
+    fn bookmarks_ui(&mut self, ui: &mut egui::Ui, bookmarks: &Bookmarks) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.bookmark_name);
+            let name = self.bookmark_name.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save"))
+                .clicked()
+            {
+                self.commands.push(Command::SaveBookmark(name.to_string()));
+                self.bookmark_name.clear();
+            }
+        });
+
+        egui::ScrollArea::vertical()
+            .max_height(320.0)
+            .show(ui, |ui| {
+                for (index, entry) in bookmarks.saved.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        self.bookmark_ui(ui, entry);
+                        if ui.small_button("Delete").clicked() {
+                            self.commands.push(Command::DeleteBookmark(index));
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label("Famous locations");
+                for entry in &bookmarks.famous {
+                    ui.horizontal(|ui| self.bookmark_ui(ui, entry));
+                }
+            });
+    }
+
+    fn bookmark_ui(&mut self, ui: &mut egui::Ui, entry: &bookmarks::Entry) {
+        let clicked = match &entry.thumbnail {
+            Some(thumbnail) => ui
+                .add(egui::ImageButton::new(
+                    thumbnail.id(),
+                    thumbnail.size_vec2(),
+                ))
+                .clicked(),
+            None => ui.spinner().clicked(),
+        };
+        if clicked | ui.link(&entry.bookmark.name).clicked() {
+            self.commands.push(Command::GoTo(entry.bookmark.view));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    io::{BufWriter, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::OnceLock,
+    thread::JoinHandle,
+};
+
+use crate::export::ExportError;
+
+const FFMPEG: &str = "ffmpeg";
+
+// Encoders offered in the interface, any other name ffmpeg knows also works
+pub const CODECS: [&str; 4] = ["libx264", "libx265", "libvpx-vp9", "libaom-av1"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VideoSettings {
+    pub codec: String,
+    // Constant rate factor, lower is better quality and larger files
+    pub crf: u32,
+    pub frame_rate: u32,
+}
+
+// Highest CRF each preset takes, other encoders are left for ffmpeg to check
+pub fn max_crf(codec: &str) -> Option<u32> {
+    match codec {
+        "libx264" | "libx265" => Some(51),
+        "libvpx-vp9" | "libaom-av1" => Some(63),
+        _ => None,
+    }
+}
+
+// Checked once, starting ffmpeg takes long enough to notice on every recording
+pub fn is_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        Command::new(FFMPEG)
+            .arg("-version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+// An ffmpeg process encoding raw RGBA frames written to its stdin
+pub struct Encoder {
+    child: Child,
+    // None once finished
+    stdin: Option<BufWriter<ChildStdin>>,
+    // Collects ffmpeg's error output, so a full pipe never stalls it
+    errors: Option<JoinHandle<String>>,
+}
+
+impl Encoder {
+    pub fn spawn(
+        path: &Path,
+        settings: &VideoSettings,
+        width: u32,
+        height: u32,
+    ) -> std::io::Result<Self> {
+        // ffmpeg would only fail once the first frame is written
+        if let Some(max) = max_crf(&settings.codec).filter(|&max| settings.crf > max) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "CRF {} is above {}, the most {} takes",
+                    settings.crf, max, settings.codec
+                ),
+            ));
+        }
+        let mut child = Command::new(FFMPEG)
+            .args(["-y", "-hide_banner", "-nostats", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &settings.frame_rate.to_string()])
+            .args(["-i", "-"])
+            .args(["-c:v", &settings.codec])
+            .args(["-crf", &settings.crf.to_string()])
+            // Most players only take 4:2:0, which needs even dimensions
+            .args([
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().map(BufWriter::new);
+        let errors = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut errors = String::new();
+                let _ = stderr.read_to_string(&mut errors);
+                errors
+            })
+        });
+        Ok(Self {
+            child,
+            stdin,
+            errors,
+        })
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u8]) -> Result<(), ExportError> {
+        let Some(stdin) = &mut self.stdin else {
+            return Ok(());
+        };
+        if let Err(e) = stdin.write_all(pixels) {
+            // ffmpeg closing its input early has usually said why
+            self.stdin = None;
+            let _ = self.child.wait();
+            return Err(match self.last_error() {
+                Some(message) => ExportError::Ffmpeg(message),
+                None => e.into(),
+            });
+        }
+        Ok(())
+    }
+
+    // Last line of ffmpeg's error output, once it has exited
+    fn last_error(&mut self) -> Option<String> {
+        let errors = self.errors.take()?.join().ok()?;
+        errors.lines().last().map(str::to_string)
+    }
+
+    // Closes the input and waits for ffmpeg to finish writing the video
+    pub fn finish(mut self) -> Result<(), ExportError> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        let status = self.child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        let message = self.last_error().unwrap_or_default();
+        Err(ExportError::Ffmpeg(format!(
+            "ffmpeg {}: {}",
+            status, message
+        )))
+    }
+}
+
+impl Drop for Encoder {
+    // An encoder dropped before finishing was cancelled or failed, its video is of no use
+    fn drop(&mut self) {
+        if self.stdin.take().is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_crf_above_the_codec_range() {
+        let settings = VideoSettings {
+            codec: String::from("libx264"),
+            crf: 52,
+            frame_rate: 30,
+        };
+        let error = Encoder::spawn(Path::new("out.mp4"), &settings, 64, 64)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(max_crf("libx264"), Some(51));
+        assert_eq!(max_crf("libaom-av1"), Some(63));
+        assert_eq!(max_crf("mpeg4"), None);
+    }
+}
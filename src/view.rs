@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+// Everything needed to return to a location: where the camera is and the formula parameters
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub centre: [f64; 2],
+    pub axis_range: f64,
+    pub iterations: i32,
+    pub value: f32,
+    pub exponent: f32,
+    // Iterates z = z^2 + seed from z = the point, rather than z^2 + the point from zero. The
+    // seed is kept while this is off, so it can be keyframed before the switch.
+    #[serde(default)]
+    pub julia: bool,
+    #[serde(default)]
+    pub seed: [f64; 2],
+}
+
+impl View {
+    // Magnification relative to the starting view
+    pub fn zoom(&self) -> f64 {
+        crate::camera::Camera::DEFAULT_RANGE / self.axis_range
+    }
+}
@@ -0,0 +1,286 @@
+// XaoS position files: a list of commands in parentheses, with ; starting a comment, e.g.
+//
+//     (initstate)
+//     (formula 'mandel)
+//     (view -0.75 0 2.5 2.5)
+//     (maxiter 170)
+
+use crate::scene::{parse_precise, Imported, Scene, SceneError};
+
+// Commands that set XaoS's own colouring and are dropped without a warning being issued for
+// each one individually
+const COLOURING_COMMANDS: &[&str] = &[
+    "palette",
+    "defaultpalette",
+    "cycling",
+    "cyclingspeed",
+    "outcoloring",
+    "incoloring",
+    "outtcoloring",
+    "intcoloring",
+];
+
+fn finish_word(word: &mut String, current: &mut Option<Vec<String>>) {
+    if let (false, Some(command)) = (word.is_empty(), current.as_mut()) {
+        command.push(std::mem::take(word));
+    }
+    word.clear();
+}
+
+// Splits the file into commands, each a list of its whitespace separated words
+fn commands(contents: &str) -> Result<Vec<Vec<String>>, SceneError> {
+    let mut commands = Vec::new();
+    let mut current: Option<Vec<String>> = None;
+    let mut word = String::new();
+    let mut in_string = false;
+
+    for line in contents.lines() {
+        for c in line.chars() {
+            match c {
+                '"' => {
+                    in_string = !in_string;
+                    word.push(c);
+                }
+                _ if in_string => word.push(c),
+                ';' => break,
+                '(' => {
+                    if current.is_some() {
+                        return Err(SceneError::Format(
+                            "nested lists are not supported".to_string(),
+                        ));
+                    }
+                    current = Some(Vec::new());
+                }
+                ')' => {
+                    finish_word(&mut word, &mut current);
+                    match current.take() {
+                        Some(command) => commands.push(command),
+                        None => return Err(SceneError::Format("unbalanced ')'".to_string())),
+                    }
+                }
+                c if c.is_whitespace() => finish_word(&mut word, &mut current),
+                c => word.push(c),
+            }
+        }
+        if !in_string {
+            finish_word(&mut word, &mut current);
+        }
+    }
+
+    if current.is_some() {
+        return Err(SceneError::Format("unbalanced '('".to_string()));
+    }
+    Ok(commands)
+}
+
+pub fn read(contents: &str) -> Result<Imported, SceneError> {
+    let mut scene = Scene::default();
+    let mut warnings = Vec::new();
+    let mut found_view = false;
+    let mut ignored_colouring = Vec::new();
+
+    for command in commands(contents)? {
+        let Some((name, arguments)) = command.split_first() else {
+            continue;
+        };
+        match (name.as_str(), arguments) {
+            // Resets XaoS to its defaults, which is where every import starts anyway
+            ("initstate", []) => {}
+            ("view", [x, y, width, height]) => {
+                let x = parse_precise("view x", x, &mut warnings);
+                let y = parse_precise("view y", y, &mut warnings);
+                let width = width.parse::<f64>().ok();
+                let height = height.parse::<f64>().ok();
+                match (x, y, width, height) {
+                    (Some(x), Some(y), Some(width), Some(height)) if height > 0.0 => {
+                        scene.view.centre = [x, y];
+                        scene.view.axis_range = height;
+                        if width != height {
+                            warnings.push(format!(
+                                "view width {} differs from height {}, the height is kept",
+                                width, height
+                            ));
+                        }
+                        found_view = true;
+                    }
+                    _ => return Err(SceneError::Format(format!("invalid view {:?}", arguments))),
+                }
+            }
+            ("maxiter", [iterations]) => match iterations.parse::<i64>() {
+                Ok(iterations) => {
+                    scene.render.iterations = iterations.clamp(1, i32::MAX as i64) as i32;
+                }
+                Err(_) => warnings.push(format!("maxiter {} is not a number", iterations)),
+            },
+            ("formula", [formula]) if formula == "'mandel" => {}
+            ("formula", [formula]) => warnings.push(format!(
+                "formula {} is not supported, rendered as the Mandelbrot set",
+                formula.trim_start_matches('\'')
+            )),
+            ("angle", [angle]) if angle.parse::<f64>().unwrap_or(0.0) == 0.0 => {}
+            ("angle", [angle]) => {
+                warnings.push(format!("angle {} is ignored, views cannot rotate", angle));
+            }
+            ("julia", [enabled]) if enabled == "#f" => {}
+            (name, _) if COLOURING_COMMANDS.contains(&name) => {
+                if !ignored_colouring.iter().any(|ignored| ignored == name) {
+                    ignored_colouring.push(name.to_string());
+                }
+            }
+            _ => warnings.push(format!(
+                "({}) is not supported and was ignored",
+                command.join(" ")
+            )),
+        }
+    }
+
+    if !ignored_colouring.is_empty() {
+        warnings.push(format!(
+            "XaoS colouring is not supported, ignored {}",
+            ignored_colouring.join(", ")
+        ));
+    }
+
+    if !found_view {
+        return Err(SceneError::Format(
+            "no (view ...) command found".to_string(),
+        ));
+    }
+
+    Ok(Imported {
+        scene: scene.validate()?,
+        warnings,
+    })
+}
+
+pub fn write(scene: &Scene) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    if !scene.formula.is_standard() {
+        warnings.push(format!(
+            "exponent {} and value {} have no XaoS equivalent, written as the Mandelbrot set",
+            scene.formula.exponent, scene.formula.value
+        ));
+    }
+    if scene.formula.julia {
+        warnings.push(
+            "Julia sets are not written to .xpf files, written as the Mandelbrot set".to_string(),
+        );
+    }
+    warnings.push("colouring is not written to .xpf files".to_string());
+
+    let [x, y] = scene.view.centre;
+    let range = scene.view.axis_range;
+    let lines = [
+        ";Position file written by mandelbrot-explorer".to_string(),
+        "(initstate)".to_string(),
+        "(formula 'mandel)".to_string(),
+        // XaoS fits both extents into its window, so a square keeps the whole height visible
+        format!("(view {} {} {} {})", x, y, range, range),
+        format!("(maxiter {})", scene.render.iterations),
+    ];
+
+    (lines.join("\n") + "\n", warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_warning(warnings: &[String], expected: &str) -> bool {
+        warnings.iter().any(|warning| warning.contains(expected))
+    }
+
+    #[test]
+    fn round_trips_through_write() {
+        let mut deep = Scene::default();
+        deep.view.centre = [-1.769383179195515, 0.0042368479187367];
+        deep.view.axis_range = 3.4e-290;
+        deep.render.iterations = 1_000_000;
+        for original in [Scene::default(), deep] {
+            let (contents, warnings) = write(&original);
+            assert_eq!(warnings, ["colouring is not written to .xpf files"]);
+            let imported = read(&contents).unwrap();
+            assert!(imported.warnings.is_empty(), "{:?}", imported.warnings);
+            assert_eq!(imported.scene.view, original.view);
+            assert_eq!(imported.scene.render, original.render);
+        }
+    }
+
+    #[test]
+    fn writes_a_julia_set_as_the_mandelbrot_set() {
+        let mut original = Scene::default();
+        original.formula.julia = true;
+        original.formula.seed = [-0.8, 0.156];
+        let (contents, warnings) = write(&original);
+        assert!(has_warning(&warnings, "Julia sets are not written"));
+        assert!(!read(&contents).unwrap().scene.formula.julia);
+    }
+
+    #[test]
+    fn writes_a_custom_formula_as_the_mandelbrot_set() {
+        let mut original = Scene::default();
+        original.formula.value = 3.0;
+        let (contents, warnings) = write(&original);
+        assert!(has_warning(&warnings, "exponent 2 and value 3"));
+        assert!(contents.contains("(formula 'mandel)"));
+    }
+
+    #[test]
+    fn reads_a_xaos_file() {
+        let imported = read(include_str!("../testdata/spiral.xpf")).unwrap();
+        let scene = imported.scene;
+        assert_eq!(scene.view.centre, [-1.7497888133, -0.000346432656]);
+        assert_eq!(scene.view.axis_range, 3.278e-6);
+        assert_eq!(scene.render.iterations, 1000);
+        assert_eq!(
+            imported.warnings,
+            ["XaoS colouring is not supported, ignored palette, outcoloring"]
+        );
+    }
+
+    #[test]
+    fn warns_about_what_does_not_carry_over() {
+        let contents = "(initstate)\n(formula 'octo)\n(view 0 0 3 2)\n(angle 45)\n\
+                        (julia #t)\n(load \"a;b\") ; not a comment inside the string\n\
+                        (maxiter lots)\n";
+        let imported = read(contents).unwrap();
+        let warnings = &imported.warnings;
+        for expected in [
+            "formula octo is not supported",
+            "view width 3 differs from height 2",
+            "angle 45 is ignored",
+            "(julia #t) is not supported",
+            "(load \"a;b\") is not supported",
+            "maxiter lots is not a number",
+        ] {
+            assert!(
+                has_warning(warnings, expected),
+                "{}: {:?}",
+                expected,
+                warnings
+            );
+        }
+        assert_eq!(warnings.len(), 6);
+        assert_eq!(imported.scene.view.axis_range, 2.0);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        for contents in [
+            "(maxiter 100)",
+            "(view 0 0 1 1",
+            "(view 0 0 1 1))",
+            "((view 0 0 1 1))",
+            "(view 0 0 1)",
+            "(view 0 0 1 0)",
+            "(view nan 0 1 1)",
+            "(view 0 0 inf inf)",
+        ] {
+            assert!(
+                matches!(read(contents), Err(SceneError::Format(_))),
+                "{}",
+                contents
+            );
+        }
+    }
+}